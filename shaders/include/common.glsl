@@ -0,0 +1,20 @@
+// Data shared by the shaders in `shaders/`. Pull it in with `#include "common.glsl"`, and
+// build the program with `.include_dir("./shaders/include")` so the file is found
+#ifndef COMMON_GLSL
+#define COMMON_GLSL
+
+// Filled in once per frame from `FrameBlock` in main.rs, which has to match this declaration
+layout(std140) uniform Frame {
+    // Takes world space to camera space
+    mat4 view;
+    // Takes camera space to clip space
+    mat4 projection;
+    // Points towards the light, in world space
+    vec3 lightDirection;
+    float ambient;
+    vec3 lightColor;
+    float iTime;
+    vec2 screenDims;
+};
+
+#endif
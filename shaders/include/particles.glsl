@@ -0,0 +1,17 @@
+// The particles of the fountain shown with P, shared by the update and draw programs
+#ifndef PARTICLES_GLSL
+#define PARTICLES_GLSL
+
+// Matches `Particle` in main.rs
+struct Particle {
+    // The w component is the number of seconds left before the particle respawns
+    vec4 position;
+    // The w component is unused
+    vec4 velocity;
+};
+
+layout(std430) buffer Particles {
+    Particle particles[];
+};
+
+#endif
@@ -0,0 +1,41 @@
+#version 430 core
+
+out vec4 color;
+in vec3 vertex_normal;
+in vec2 vertex_uv;
+
+#include "common.glsl"
+
+// The metallic-roughness material of the mesh. The textures are multiplied with the factors
+uniform vec4 baseColor;
+uniform sampler2D baseColorTexture;
+uniform float metallic;
+uniform float roughness;
+// Roughness in the green channel, metalness in the blue channel
+uniform sampler2D metallicRoughnessTexture;
+uniform vec3 emissive;
+// Fragments with less alpha are discarded
+uniform float alphaCutoff;
+
+void main()
+{
+    vec4 albedo = baseColor * texture(baseColorTexture, vertex_uv);
+    if (albedo.a < alphaCutoff) {
+        discard;
+    }
+    vec4 metallic_roughness = texture(metallicRoughnessTexture, vertex_uv);
+    float metalness = metallic * metallic_roughness.b;
+    float rough = roughness * metallic_roughness.g;
+
+    vec3 normal = normalize(vertex_normal);
+    if (!gl_FrontFacing) {
+        normal = -normal;
+    }
+    vec3 light = ambient + lightColor * max(dot(normal, normalize(lightDirection)), 0.0);
+
+    // Without the view direction there are no highlights, but metals still trade their diffuse
+    // light for reflections tinted by their base color, and smoother surfaces reflect more
+    vec3 diffuse = albedo.rgb * (1.0 - metalness);
+    vec3 specular = mix(vec3(0.04), albedo.rgb, metalness) * (1.0 - rough);
+    color = vec4((diffuse + specular) * light + emissive, albedo.a);
+}
@@ -0,0 +1,18 @@
+#version 430 core
+
+#include "common.glsl"
+
+// Places the mesh in the world
+uniform mat4 model;
+
+layout(location = 0) in vec3 position;
+layout(location = 2) in vec3 normal;
+layout(location = 3) in vec2 uv;
+out vec3 vertex_normal;
+out vec2 vertex_uv;
+void main()
+{
+    vertex_normal = transpose(inverse(mat3(model))) * normal;
+    vertex_uv = uv;
+    gl_Position = projection * view * model * vec4(position, 1.0f);
+}
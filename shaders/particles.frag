@@ -0,0 +1,9 @@
+#version 430 core
+
+in float fade;
+out vec4 color;
+
+void main()
+{
+    color = vec4(1.0, 0.8, 0.4, fade);
+}
@@ -0,0 +1,14 @@
+#version 430 core
+
+#include "common.glsl"
+#include "particles.glsl"
+
+out float fade;
+
+// Drawn without any vertex attributes, with one point per particle
+void main()
+{
+    Particle p = particles[gl_VertexID];
+    fade = clamp(p.position.w, 0.0, 1.0);
+    gl_Position = projection * view * vec4(p.position.xyz, 1.0);
+}
@@ -2,12 +2,22 @@
 
 out vec4 color;
 in vec4 vertex_color;
+in vec3 world_position;
 
-uniform vec2 screenDims;
-uniform float iTime;
+#include "common.glsl"
 
 void main()
 {
-
+#if defined(DEBUG_DEPTH)
+    // Build with `.define("DEBUG_DEPTH", "1")` to visualise depth instead of vertex colors
+    color = vec4(vec3(gl_FragCoord.z), 1.0);
+#elif defined(LIT)
+    // Build with `.define("LIT", "1")` for flat shading, with the face normal taken from how the
+    // position changes between neighbouring fragments
+    vec3 normal = normalize(cross(dFdx(world_position), dFdy(world_position)));
+    float lambert = max(dot(normal, lightDirection), 0.0);
+    color = vec4(vertex_color.rgb * (ambient + lightColor * lambert), vertex_color.a);
+#else
     color = vertex_color;
+#endif
 }
\ No newline at end of file
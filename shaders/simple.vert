@@ -1,20 +1,18 @@
 #version 430 core
 
-uniform float iTime;
-uniform mat4 camera;
+#include "common.glsl"
+
+// Places the triangles in the world
+uniform mat4 model;
 
 layout(location = 0) in vec3 position;
 layout(location = 1) in vec4 color;
 out vec4 vertex_color;
+out vec3 world_position;
 void main()
 {
-    mat4 trans_mat = mat4(
-        1.0, 0.0, 0.0, 0.0,
-        0.0, 1.0, 0.0, 0.0,
-        0.0, 0.0, 1.0, 0.0,
-        0.0, 0.0, 0.0, 1.0
-    );
-
+    vec4 world = model * vec4(position, 1.0f);
     vertex_color = color;
-    gl_Position = camera * trans_mat * vec4(position, 1.0f);
+    world_position = world.xyz;
+    gl_Position = projection * view * world;
 }
\ No newline at end of file
@@ -0,0 +1,11 @@
+#version 430 core
+
+out vec4 color;
+in vec3 view_direction;
+
+uniform samplerCube skybox;
+
+void main()
+{
+    color = texture(skybox, normalize(view_direction));
+}
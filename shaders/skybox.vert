@@ -0,0 +1,21 @@
+#version 430 core
+
+// Maps clip space back to world space
+uniform mat4 inverseViewProjection;
+
+out vec3 view_direction;
+void main()
+{
+    // A triangle covering the whole screen, built from the vertex index so no buffers are needed
+    vec2 corner = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2) * 2.0 - 1.0;
+
+    // The direction from the near plane to the far plane through this corner, which only depends
+    // on the rotation of the view. Scaling by the w of both ends instead of dividing by them keeps
+    // this working for infinite projections, where w is 0 on the far plane
+    vec4 near = inverseViewProjection * vec4(corner, -1.0, 1.0);
+    vec4 far = inverseViewProjection * vec4(corner, 1.0, 1.0);
+    view_direction = far.xyz * near.w - near.xyz * far.w;
+
+    // z = w puts the sky at the maximum depth, behind everything else
+    gl_Position = vec4(corner, 1.0, 1.0);
+}
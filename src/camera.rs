@@ -0,0 +1,143 @@
+// A perspective camera placed in the world by a position and yaw, pitch and roll angles.
+//
+// With all angles at zero the camera looks down -Z with +Y up. Positive yaw turns it to the left
+// around the world's Y axis, positive pitch tilts it up, and positive roll banks it to the left.
+// The angles are in radians, but the field of view is in degrees.
+
+pub const MIN_FOV: f32 = 1.0;
+pub const MAX_FOV: f32 = 179.0;
+
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub position: glm::Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub roll: f32,
+    // Vertical field of view in degrees
+    fov: f32,
+    near: f32,
+    far: f32,
+    // Width divided by height of the viewport
+    aspect: f32,
+}
+
+impl Camera {
+    pub fn new(aspect: f32) -> Camera {
+        Camera {
+            position: glm::Vec3::zeros(),
+            yaw: 0.0,
+            pitch: 0.0,
+            roll: 0.0,
+            fov: 60.0,
+            near: 0.1,
+            far: 100.0,
+            aspect,
+        }
+    }
+
+    pub fn fov(&self) -> f32 {
+        self.fov
+    }
+
+    // Clamped to [MIN_FOV, MAX_FOV] degrees, outside of which the projection degenerates
+    pub fn set_fov(&mut self, degrees: f32) {
+        self.fov = degrees.clamp(MIN_FOV, MAX_FOV);
+    }
+
+    pub fn near(&self) -> f32 {
+        self.near
+    }
+
+    pub fn far(&self) -> f32 {
+        self.far
+    }
+
+    // The near plane has to be in front of the camera and the far plane beyond it, so bad values
+    // are nudged into a valid range instead of producing a broken projection
+    pub fn set_clip_planes(&mut self, near: f32, far: f32) {
+        self.near = near.max(f32::EPSILON);
+        self.far = if far > self.near { far } else { self.near * 2.0 };
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+
+    pub fn set_aspect(&mut self, width: u32, height: u32) {
+        self.aspect = width as f32 / height.max(1) as f32;
+    }
+
+    // The rotation taking directions from camera space to world space
+    pub fn orientation(&self) -> glm::Mat4 {
+        let yaw = glm::rotation(self.yaw, &glm::vec3(0.0, 1.0, 0.0));
+        let pitch = glm::rotation(self.pitch, &glm::vec3(1.0, 0.0, 0.0));
+        let roll = glm::rotation(self.roll, &glm::vec3(0.0, 0.0, 1.0));
+        yaw * pitch * roll
+    }
+
+    fn direction(&self, local: glm::Vec3) -> glm::Vec3 {
+        (self.orientation() * local.push(0.0)).xyz()
+    }
+
+    // The direction the camera is looking in
+    pub fn forward(&self) -> glm::Vec3 {
+        self.direction(glm::vec3(0.0, 0.0, -1.0))
+    }
+
+    pub fn right(&self) -> glm::Vec3 {
+        self.direction(glm::vec3(1.0, 0.0, 0.0))
+    }
+
+    pub fn up(&self) -> glm::Vec3 {
+        self.direction(glm::vec3(0.0, 1.0, 0.0))
+    }
+
+    // Places the camera like a node of a glTF scene, which looks down its local -Z with +Y up.
+    // Any scale in `transform` is ignored
+    pub fn set_transform(&mut self, transform: &glm::Mat4) {
+        self.position = transform.column(3).xyz();
+        let axis = |column: usize| glm::normalize(&transform.column(column).xyz());
+        let (x, y, z) = (axis(0), axis(1), axis(2));
+        // `orientation` multiplies out to these entries of the rotation
+        self.yaw = z.x.atan2(z.z);
+        self.pitch = (-z.y).clamp(-1.0, 1.0).asin();
+        self.roll = x.y.atan2(y.y);
+    }
+
+    // Takes world space to camera space
+    pub fn view(&self) -> glm::Mat4 {
+        // The orientation is a pure rotation, so its inverse is its transpose
+        glm::transpose(&self.orientation()) * glm::translation(&-self.position)
+    }
+
+    // Takes camera space to clip space
+    pub fn projection(&self) -> glm::Mat4 {
+        glm::perspective(self.aspect, self.fov.to_radians(), self.near, self.far)
+    }
+
+    pub fn view_projection(&self) -> glm::Mat4 {
+        self.projection() * self.view()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transform_round_trip() {
+        let mut camera = Camera::new(1.0);
+        camera.position = glm::vec3(1.0, 2.0, 3.0);
+        camera.yaw = 2.5;
+        camera.pitch = -0.7;
+        camera.roll = 0.3;
+        let transform = glm::translation(&camera.position) * camera.orientation();
+
+        let mut placed = Camera::new(1.0);
+        placed.set_transform(&(transform * glm::scaling(&glm::vec3(2.0, 2.0, 2.0))));
+        assert!(glm::distance(&placed.position, &camera.position) < 1e-5);
+        assert!((placed.yaw - camera.yaw).abs() < 1e-5);
+        assert!((placed.pitch - camera.pitch).abs() < 1e-5);
+        assert!((placed.roll - camera.roll).abs() < 1e-5);
+    }
+}
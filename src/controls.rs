@@ -0,0 +1,129 @@
+// Ways of steering a `Camera` with the keyboard and mouse.
+//
+// Controllers read the keys held down and the mouse movement the event loop has gathered since the
+// previous frame. Everything is scaled by the frame time, so the camera moves at the same speed
+// regardless of the frame rate.
+
+use glutin::event::{MouseButton, VirtualKeyCode};
+
+use crate::camera::Camera;
+
+// Looking straight up or down would make the view direction parallel to the up axis
+const MAX_PITCH: f32 = 89.0 * std::f32::consts::PI / 180.0;
+
+// A first person camera that flies where it looks. WASD moves along the view direction and
+// sideways, space and shift move straight up and down, and the mouse turns
+#[derive(Clone, Debug)]
+pub struct FlyController {
+    // Radians turned per pixel of mouse movement
+    pub sensitivity: f32,
+    // World units per second
+    pub speed: f32,
+}
+
+impl FlyController {
+    pub fn new(sensitivity: f32) -> FlyController {
+        FlyController {
+            sensitivity,
+            speed: 3.0,
+        }
+    }
+
+    // `mouse_delta` is the movement in pixels since the last update
+    pub fn update(
+        &self,
+        camera: &mut Camera,
+        keys: &[VirtualKeyCode],
+        mouse_delta: (f32, f32),
+        delta_time: f32,
+    ) {
+        // Moving the mouse right turns right and moving it down looks down, so both go against
+        // the direction the angles turn in
+        camera.yaw -= mouse_delta.0 * self.sensitivity;
+        camera.pitch = (camera.pitch - mouse_delta.1 * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+
+        let up = glm::vec3(0.0, 1.0, 0.0);
+        let mut direction = glm::Vec3::zeros();
+        for key in keys {
+            match key {
+                VirtualKeyCode::W => direction += camera.forward(),
+                VirtualKeyCode::S => direction -= camera.forward(),
+                VirtualKeyCode::D => direction += camera.right(),
+                VirtualKeyCode::A => direction -= camera.right(),
+                VirtualKeyCode::Space => direction += up,
+                VirtualKeyCode::LShift => direction -= up,
+                _ => {}
+            }
+        }
+        // Moving diagonally shouldn't be faster than moving straight
+        if direction.norm_squared() > 0.0 {
+            camera.position += direction.normalize() * self.speed * delta_time;
+        }
+    }
+}
+
+// Circles a target point, for looking at a model from every side. Dragging with the left button
+// turns around the target, scrolling zooms in and out, and dragging with the middle button pans
+#[derive(Clone, Debug)]
+pub struct OrbitController {
+    pub target: glm::Vec3,
+    // From the target to the camera
+    pub distance: f32,
+    // Radians turned per pixel dragged
+    pub sensitivity: f32,
+    // The fraction of the distance each scroll step zooms in by
+    pub zoom_step: f32,
+}
+
+impl OrbitController {
+    // Orbits a point `distance` in front of the camera, so switching over doesn't move the view
+    pub fn from_camera(camera: &Camera, distance: f32) -> OrbitController {
+        OrbitController {
+            target: camera.position + camera.forward() * distance,
+            distance,
+            sensitivity: 0.005,
+            zoom_step: 0.1,
+        }
+    }
+
+    // `mouse_delta` is the movement in pixels and `scroll` the number of lines scrolled since the
+    // last update, positive when scrolling away from the user
+    pub fn update(
+        &mut self,
+        camera: &mut Camera,
+        buttons: &[MouseButton],
+        mouse_delta: (f32, f32),
+        scroll: f32,
+    ) {
+        if buttons.contains(&MouseButton::Left) {
+            camera.yaw -= mouse_delta.0 * self.sensitivity;
+            camera.pitch =
+                (camera.pitch - mouse_delta.1 * self.sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+        }
+        if buttons.contains(&MouseButton::Middle) {
+            // Move the target so the point under the cursor roughly follows it. How far a pixel
+            // reaches grows with the distance to what is being looked at
+            let pixel = self.distance * (camera.fov().to_radians() / 2.0).tan() / 300.0;
+            self.target -= camera.right() * mouse_delta.0 * pixel;
+            self.target += camera.up() * mouse_delta.1 * pixel;
+        }
+        self.distance = (self.distance * (1.0 - self.zoom_step).powf(scroll)).max(camera.near());
+        self.place(camera);
+    }
+
+    // Aims at the middle of the box and backs off until all of it fits on screen
+    pub fn frame(&mut self, camera: &mut Camera, (min, max): (glm::Vec3, glm::Vec3)) {
+        self.target = (min + max) / 2.0;
+        let radius = glm::distance(&min, &max) / 2.0;
+        // The field of view is vertical, so the narrower of the two angles decides
+        let half_fov = (camera.fov().to_radians() / 2.0).tan();
+        let half_fov = half_fov.min(half_fov * camera.aspect()).atan();
+        self.distance = (radius / half_fov.sin()).max(camera.near());
+        self.place(camera);
+    }
+
+    // Puts the camera `distance` behind the target along its view direction
+    fn place(&self, camera: &mut Camera) {
+        camera.position = self.target - camera.forward() * self.distance;
+    }
+}
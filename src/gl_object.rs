@@ -0,0 +1,140 @@
+// Owned handles to OpenGL objects, which delete the object they hold when dropped.
+//
+// GL objects belong to the context they were created in, and that context is only current on the
+// render thread. The handles are therefore neither `Send` nor `Sync`, so they can't accidentally be
+// dropped on a thread where deleting them would do nothing.
+
+use std::marker::PhantomData;
+
+// Opts a type out of `Send` and `Sync`
+type NotThreadSafe = PhantomData<*const ()>;
+
+macro_rules! gl_object {
+    ($(#[$attr:meta])* $name:ident, |$id:ident| $delete:expr) => {
+        $(#[$attr])*
+        pub struct $name {
+            id: u32,
+            _not_thread_safe: NotThreadSafe,
+        }
+
+        impl $name {
+            pub fn id(&self) -> u32 {
+                self.id
+            }
+        }
+
+        impl Drop for $name {
+            fn drop(&mut self) {
+                let $id = self.id;
+                unsafe { $delete };
+            }
+        }
+    };
+}
+
+gl_object!(Program, |id| gl::DeleteProgram(id));
+gl_object!(Buffer, |id| gl::DeleteBuffers(1, &id));
+gl_object!(Framebuffer, |id| gl::DeleteFramebuffers(1, &id));
+
+// Vertex arrays also keep the buffers they read from alive
+pub struct VertexArray {
+    id: u32,
+    buffers: Vec<Buffer>,
+    _not_thread_safe: NotThreadSafe,
+}
+
+pub struct Texture {
+    id: u32,
+    _not_thread_safe: NotThreadSafe,
+}
+
+impl Program {
+    pub unsafe fn new() -> Program {
+        Program {
+            id: gl::CreateProgram(),
+            _not_thread_safe: PhantomData,
+        }
+    }
+}
+
+impl Buffer {
+    pub unsafe fn new() -> Buffer {
+        let mut id = 0;
+        gl::CreateBuffers(1, &mut id);
+        Buffer {
+            id,
+            _not_thread_safe: PhantomData,
+        }
+    }
+}
+
+impl Framebuffer {
+    pub unsafe fn new() -> Framebuffer {
+        let mut id = 0;
+        gl::CreateFramebuffers(1, &mut id);
+        Framebuffer {
+            id,
+            _not_thread_safe: PhantomData,
+        }
+    }
+}
+
+impl VertexArray {
+    pub unsafe fn new() -> VertexArray {
+        let mut id = 0;
+        gl::CreateVertexArrays(1, &mut id);
+        VertexArray {
+            id,
+            buffers: vec![],
+            _not_thread_safe: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    // Hands ownership of a buffer the vertex array reads from over to it, so the buffer lives
+    // exactly as long as the vertex array
+    pub fn keep_buffer(&mut self, buffer: Buffer) {
+        self.buffers.push(buffer);
+    }
+
+    pub unsafe fn bind(&self) {
+        gl::BindVertexArray(self.id);
+    }
+}
+
+impl Drop for VertexArray {
+    // The buffers are dropped after this, once nothing refers to them anymore
+    fn drop(&mut self) {
+        unsafe { gl::DeleteVertexArrays(1, &self.id) };
+    }
+}
+
+impl Texture {
+    // Creates a texture for `target`, e.g. `gl::TEXTURE_2D`
+    pub unsafe fn new(target: gl::types::GLenum) -> Texture {
+        let mut id = 0;
+        gl::CreateTextures(target, 1, &mut id);
+        Texture {
+            id,
+            _not_thread_safe: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    // Binds the texture to texture unit `unit`
+    pub unsafe fn bind(&self, unit: u32) {
+        gl::BindTextureUnit(unit, self.id);
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &self.id) };
+    }
+}
@@ -0,0 +1,120 @@
+// Rendering without a window, for machines without a display such as CI runners. The tests that
+// need a driver use the same contexts.
+//
+// Frames are drawn into an offscreen `RenderTarget` by the same `Renderer` the window uses, and
+// written to disk as numbered PNG files or a Y4M video. Time advances by a fixed step per frame
+// instead of following the clock, so every run produces the same images.
+//
+// On Linux the context comes from OSMesa if it is installed, or else from EGL's surfaceless
+// platform. Neither needs a display, and both run on Mesa's llvmpipe when there is no GPU.
+
+use std::os::raw::c_void;
+
+use glutin::dpi::PhysicalSize;
+use glutin::{Api, Context, GlProfile, GlRequest, PossiblyCurrent};
+
+use crate::options::Options;
+use crate::recording::Recorder;
+use crate::renderer::{self, Renderer};
+
+#[cfg(target_os = "linux")]
+mod egl;
+
+// A context that is current on this thread
+enum HeadlessContext {
+    Glutin {
+        context: Context<PossiblyCurrent>,
+        // Contexts built from an event loop may depend on it staying around
+        _event_loop: Option<glutin::event_loop::EventLoop<()>>,
+    },
+    #[cfg(target_os = "linux")]
+    Surfaceless(egl::SurfacelessContext),
+}
+
+impl HeadlessContext {
+    fn get_proc_address(&self, name: &str) -> *const c_void {
+        match self {
+            HeadlessContext::Glutin { context, .. } => context.get_proc_address(name) as *const _,
+            #[cfg(target_os = "linux")]
+            HeadlessContext::Surfaceless(context) => context.get_proc_address(name),
+        }
+    }
+}
+
+// The renderer relies on direct state access, which became core in 4.5
+const GL_VERSION: (u8, u8) = (4, 5);
+
+fn context_builder() -> glutin::ContextBuilder<'static, glutin::NotCurrent> {
+    glutin::ContextBuilder::new()
+        .with_gl(GlRequest::Specific(Api::OpenGl, GL_VERSION))
+        .with_gl_profile(GlProfile::Core)
+}
+
+#[cfg(target_os = "linux")]
+fn create_context(size: PhysicalSize<u32>) -> Result<HeadlessContext, String> {
+    use glutin::platform::unix::HeadlessContextExt;
+
+    let osmesa_error = match context_builder().build_osmesa(size) {
+        Ok(context) => {
+            let context = unsafe { context.make_current() }.map_err(|(_, e)| e.to_string())?;
+            return Ok(HeadlessContext::Glutin {
+                context,
+                _event_loop: None,
+            });
+        }
+        Err(e) => e,
+    };
+    println!("WARNING: no OSMesa context ({}), trying surfaceless EGL", osmesa_error);
+
+    let (major, minor) = GL_VERSION;
+    unsafe { egl::SurfacelessContext::new(major.into(), minor.into()) }
+        .map(HeadlessContext::Surfaceless)
+        .map_err(|e| format!("OSMesa failed ({}) and so did surfaceless EGL ({})", osmesa_error, e))
+}
+
+// Elsewhere glutin's headless contexts use a hidden window, so a display is still needed
+#[cfg(not(target_os = "linux"))]
+fn create_context(size: PhysicalSize<u32>) -> Result<HeadlessContext, String> {
+    let event_loop = glutin::event_loop::EventLoop::new();
+    let context = context_builder()
+        .build_headless(&event_loop, size)
+        .map_err(|e| e.to_string())?;
+    let context = unsafe { context.make_current() }.map_err(|(_, e)| e.to_string())?;
+    Ok(HeadlessContext::Glutin {
+        context,
+        _event_loop: Some(event_loop),
+    })
+}
+
+// Renders `options.frames` frames, starting `options.start_time` seconds in, and records them to
+// `options.output`
+pub fn run(options: &Options) -> Result<(), String> {
+    let (width, height) = options.size;
+    let headless = create_context(PhysicalSize::new(width, height))?;
+    gl::load_with(|symbol| headless.get_proc_address(symbol));
+
+    let recorder = Recorder::start(&options.output, options.frame_rate)?;
+
+    unsafe {
+        renderer::setup_gl();
+        let mut renderer = Renderer::offscreen(options, width, height);
+        for frame in 0..options.frames {
+            renderer.draw(options.start_time + frame as f32 / options.frame_rate);
+            recorder.record(renderer.read_pixels());
+        }
+    }
+    let frames = recorder.finish()?;
+    println!("Wrote {} frames to {}", frames, options.output);
+    Ok(())
+}
+
+// Runs `test` with a small headless context current on the calling thread. The `gl` function
+// pointers are global and tests run in parallel, so only one test holds a context at a time
+#[cfg(test)]
+pub fn with_test_context(test: impl FnOnce()) {
+    static CONTEXT_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    let _lock = CONTEXT_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let headless = create_context(PhysicalSize::new(1, 1)).unwrap_or_else(|e| panic!("{}", e));
+    gl::load_with(|symbol| headless.get_proc_address(symbol));
+    test();
+}
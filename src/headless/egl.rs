@@ -0,0 +1,108 @@
+// An OpenGL context without any display, through EGL's surfaceless platform
+// (`EGL_MESA_platform_surfaceless`). The context has no default framebuffer, so everything has to
+// be drawn into framebuffer objects.
+
+use std::{ffi::CString, os::raw::c_void, ptr};
+
+use glutin_egl_sys::egl::{self, types::EGLint};
+use libloading::Library;
+
+// Not part of the bindings glutin generates
+const PLATFORM_SURFACELESS_MESA: egl::types::EGLenum = 0x31DD;
+
+pub struct SurfacelessContext {
+    egl: egl::Egl,
+    display: egl::types::EGLDisplay,
+    context: egl::types::EGLContext,
+    // Declared last so the library is unloaded after the context is destroyed
+    _library: Library,
+}
+
+impl SurfacelessContext {
+    // Creates a core profile context of the given version and makes it current on this thread
+    pub unsafe fn new(major: EGLint, minor: EGLint) -> Result<SurfacelessContext, String> {
+        let library = Library::new("libEGL.so.1").map_err(|e| format!("could not load libEGL: {}", e))?;
+        let egl = egl::Egl::load_with(|name| {
+            let name = CString::new(name).unwrap();
+            library
+                .get::<*const c_void>(name.as_bytes_with_nul())
+                .map_or(ptr::null(), |symbol| *symbol)
+        });
+
+        let display = egl.GetPlatformDisplay(
+            PLATFORM_SURFACELESS_MESA,
+            egl::DEFAULT_DISPLAY as *mut c_void,
+            ptr::null(),
+        );
+        if display == egl::NO_DISPLAY {
+            return Err("EGL has no surfaceless platform".to_string());
+        }
+        let (mut egl_major, mut egl_minor) = (0, 0);
+        if egl.Initialize(display, &mut egl_major, &mut egl_minor) == egl::FALSE {
+            return Err(format!("could not initialize EGL, error 0x{:x}", egl.GetError()));
+        }
+
+        // With no surfaces to draw into, any surface type will do
+        let config_attributes = [
+            egl::SURFACE_TYPE as EGLint,
+            0,
+            egl::RENDERABLE_TYPE as EGLint,
+            egl::OPENGL_BIT as EGLint,
+            egl::NONE as EGLint,
+        ];
+        let mut config = ptr::null();
+        let mut config_count = 0;
+        let found = egl.ChooseConfig(display, config_attributes.as_ptr(), &mut config, 1, &mut config_count);
+        if found == egl::FALSE || config_count == 0 {
+            egl.Terminate(display);
+            return Err("EGL has no config for desktop OpenGL".to_string());
+        }
+
+        egl.BindAPI(egl::OPENGL_API);
+        let context_attributes = [
+            egl::CONTEXT_MAJOR_VERSION as EGLint,
+            major,
+            egl::CONTEXT_MINOR_VERSION as EGLint,
+            minor,
+            egl::CONTEXT_OPENGL_PROFILE_MASK as EGLint,
+            egl::CONTEXT_OPENGL_CORE_PROFILE_BIT as EGLint,
+            egl::NONE as EGLint,
+        ];
+        let context = egl.CreateContext(display, config, egl::NO_CONTEXT, context_attributes.as_ptr());
+        if context == egl::NO_CONTEXT {
+            let error = egl.GetError();
+            egl.Terminate(display);
+            return Err(format!(
+                "could not create an OpenGL {}.{} context, error 0x{:x}",
+                major, minor, error
+            ));
+        }
+
+        let surfaceless = SurfacelessContext {
+            egl,
+            display,
+            context,
+            _library: library,
+        };
+        let egl = &surfaceless.egl;
+        if egl.MakeCurrent(display, egl::NO_SURFACE, egl::NO_SURFACE, context) == egl::FALSE {
+            return Err(format!("could not make the context current, error 0x{:x}", egl.GetError()));
+        }
+        Ok(surfaceless)
+    }
+
+    pub fn get_proc_address(&self, name: &str) -> *const c_void {
+        let name = CString::new(name).unwrap();
+        unsafe { self.egl.GetProcAddress(name.as_ptr()) as *const c_void }
+    }
+}
+
+impl Drop for SurfacelessContext {
+    fn drop(&mut self) {
+        unsafe {
+            self.egl.MakeCurrent(self.display, egl::NO_SURFACE, egl::NO_SURFACE, egl::NO_CONTEXT);
+            self.egl.DestroyContext(self.display, self.context);
+            self.egl.Terminate(self.display);
+        }
+    }
+}
@@ -1,120 +1,100 @@
 extern crate nalgebra_glm as glm;
-use std::convert::TryInto;
 use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
-use std::{mem, os::raw::c_void, ptr};
-
-use std::ffi::CString;
-
-use std::collections::HashSet;
 
+mod headless;
+mod camera;
+mod controls;
+mod gl_object;
+mod mesh;
+mod options;
+mod particles;
+mod recording;
+mod render_target;
+mod renderer;
+mod scene;
+mod screenshot;
 mod shader;
+mod skybox;
+mod texture;
 mod util;
+mod vertex;
+
+use controls::{FlyController, OrbitController};
+use options::Options;
+use recording::Recorder;
+use renderer::Renderer;
 
 use glutin::event::{
     DeviceEvent,
     ElementState::{Pressed, Released},
-    Event, KeyboardInput,
+    Event, KeyboardInput, MouseButton, MouseScrollDelta,
     VirtualKeyCode::{self, *},
     WindowEvent,
 };
+use glutin::dpi::PhysicalSize;
 use glutin::event_loop::ControlFlow;
 
 const SCREEN_W: u32 = 800;
 const SCREEN_H: u32 = 600;
 
-// == // Helper functions to make interacting with OpenGL a little bit prettier. You *WILL* need these! // == //
-// The names should be pretty self explanatory
-fn byte_size_of_array<T>(val: &[T]) -> isize {
-    std::mem::size_of_val(&val[..]) as isize
-}
-
-// Get the OpenGL-compatible pointer to an arbitrary array of numbers
-fn pointer_to_array<T>(val: &[T]) -> *const c_void {
-    &val[0] as *const T as *const c_void
-}
-
-// Get the size of the given type in bytes
-fn size_of<T>() -> i32 {
-    mem::size_of::<T>() as i32
-}
-
-// Get an offset in bytes for n units of type T
-fn offset<T>(n: u32) -> *const c_void {
-    (n * mem::size_of::<T>() as u32) as *const T as *const c_void
-}
-
-// Get a null pointer (equivalent to an offset of 0)
-// ptr::null()
-
-// == // Modify and complete the function below for the first task
-// unsafe fn FUNCTION_NAME(ARGUMENT_NAME: &Vec<f32>, ARGUMENT_NAME: &Vec<u32>) -> u32 { }
-
-unsafe fn setup_triangle_vao(verticies: &Vec<f32>, indicies: &Vec<u32>, colors:&Vec<f32>) -> u32 {
-    let mut vao_id = 0;
-    gl::GenVertexArrays(1, &mut vao_id);
-    gl::BindVertexArray(vao_id);
-
-    // Load verticies (vertex attrib 0)
-    let mut vert_buffer_id = 0;
-    gl::GenBuffers(1, &mut vert_buffer_id);
-    gl::BindBuffer(gl::ARRAY_BUFFER, vert_buffer_id);
-
-    let c_v_ptr = pointer_to_array(verticies);
-    let c_v_size = byte_size_of_array(verticies);
-    gl::BufferData(gl::ARRAY_BUFFER, c_v_size, c_v_ptr, gl::STATIC_DRAW);
-    
-    gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, 0 as *const c_void);
-    gl::EnableVertexAttribArray(0);
-
-    // Load vertex colors (vertex attrib 1)
-    let mut color_buffer_id = 0;
-    gl::GenBuffers(1, &mut color_buffer_id);
-    gl::BindBuffer(gl::ARRAY_BUFFER, color_buffer_id);
-
-    let c_c_ptr = pointer_to_array(colors);
-    let c_c_size = byte_size_of_array(colors);
-    gl::BufferData(gl::ARRAY_BUFFER, c_c_size, c_c_ptr, gl::STATIC_DRAW);
-    
-    gl::VertexAttribPointer(1, 4, gl::FLOAT, gl::FALSE, 0, 0 as *const c_void);
-    gl::EnableVertexAttribArray(1);
-
-    // Load indicies
-    let mut element_buf_id = 0;
-    gl::GenBuffers(1, &mut element_buf_id);
-    gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, element_buf_id);
-
-    let index_v_size = byte_size_of_array(indicies);
-    gl::BufferData(
-        gl::ELEMENT_ARRAY_BUFFER,
-        index_v_size,
-        pointer_to_array(indicies),
-        gl::STATIC_DRAW,
-    );
-
-
-    vao_id
+// Which controller steers the camera. C switches between them
+enum CameraMode {
+    Fly,
+    Orbit(OrbitController),
 }
 
-
 fn main() {
+    let options = Options::from_args();
+    if options.headless {
+        if let Err(e) = headless::run(&options) {
+            eprintln!("Headless rendering failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // Set up the necessary objects to deal with windows and event handling
     let el = glutin::event_loop::EventLoop::new();
     let wb = glutin::window::WindowBuilder::new()
         .with_title("Gloom-rs")
-        .with_resizable(false)
+        .with_resizable(true)
         .with_inner_size(glutin::dpi::LogicalSize::new(SCREEN_W, SCREEN_H));
-    let cb = glutin::ContextBuilder::new().with_vsync(true);
+    // An sRGB capable back buffer, which `FRAMEBUFFER_SRGB` encodes the linear shader output for
+    let cb = glutin::ContextBuilder::new().with_vsync(true).with_srgb(true);
     let windowed_context = cb.build_windowed(wb, &el).unwrap();
-    // Uncomment these if you want to use the mouse for controls, but want it to be confined to the screen and/or invisible.
-    // windowed_context.window().set_cursor_grab(true).expect("failed to grab cursor");
-    // windowed_context.window().set_cursor_visible(false);
+    // Confine the mouse to the window and hide it, so looking around doesn't run the cursor into
+    // the edge of the screen. Pass `--grab-cursor` to enable
+    if options.grab_cursor {
+        windowed_context.window().set_cursor_grab(true).expect("failed to grab cursor");
+        windowed_context.window().set_cursor_visible(false);
+    }
+
+    // The window size in physical pixels, which is what OpenGL works in. On HiDPI screens this is
+    // larger than the logical size the window was created with
+    let initial_size = windowed_context.window().inner_size();
+
+    // Set up a shared slot for the latest window size, filled in when the window is resized and
+    // emptied by the render thread once it has caught up
+    let arc_resized = Arc::new(Mutex::new(None::<PhysicalSize<u32>>));
+    // Make a reference of this slot to send to the render thread
+    let resized = Arc::clone(&arc_resized);
 
     // Set up a shared vector for keeping track of currently pressed keys
     let arc_pressed_keys = Arc::new(Mutex::new(Vec::<VirtualKeyCode>::with_capacity(10)));
     // Make a reference of this vector to send to the render thread
     let pressed_keys = Arc::clone(&arc_pressed_keys);
 
+    // Set up a shared vector for keeping track of currently pressed mouse buttons
+    let arc_mouse_buttons = Arc::new(Mutex::new(Vec::<MouseButton>::with_capacity(3)));
+    // Make a reference of this vector to send to the render thread
+    let mouse_buttons = Arc::clone(&arc_mouse_buttons);
+
+    // Set up a shared number of lines scrolled with the mouse wheel between frames
+    let arc_scroll_delta = Arc::new(Mutex::new(0f32));
+    // Make a reference of this number to send to the render thread
+    let scroll_delta = Arc::clone(&arc_scroll_delta);
+
     // Set up shared tuple for tracking mouse movement between frames
     let arc_mouse_delta = Arc::new(Mutex::new((0f32, 0f32)));
     // Make a reference of this tuple to send to the render thread
@@ -130,175 +110,147 @@ fn main() {
             c
         };
 
-        // Set up openGL
-        unsafe {
-            gl::Enable(gl::DEPTH_TEST);
-            gl::DepthFunc(gl::LESS);
-            gl::Enable(gl::CULL_FACE);
-            gl::Disable(gl::MULTISAMPLE);
-            gl::Enable(gl::BLEND);
-            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
-            gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
-            gl::DebugMessageCallback(Some(util::debug_callback), ptr::null());
-
-            // Print some diagnostics
-            println!(
-                "{}: {}",
-                util::get_gl_string(gl::VENDOR),
-                util::get_gl_string(gl::RENDERER)
-            );
-            println!("OpenGL\t: {}", util::get_gl_string(gl::VERSION));
-            println!(
-                "GLSL\t: {}",
-                util::get_gl_string(gl::SHADING_LANGUAGE_VERSION)
-            );
-        }
-
-        // == // Set up your VAO here
-        let verticies: Vec<f32> = vec![
-            // Triangle 0
-             0.0,  1.0,  -1.5,
-            -1.0, -1.0,  -1.5,
-             1.0, -1.0,  -1.5,
-             // Triangle 1
-             0.0,  0.5,  -1.25,
-            -0.5, -0.5,  -1.25,
-             0.5, -0.5,  -1.25,
-             // Triangle 2
-             0.0,   0.25,  -1.0,
-            -0.25, -0.25,  -1.0,
-             0.25, -0.25,  -1.0,
-        ];
-        let indicies: Vec<u32> = vec![
-            // Triangle 0    
-            0, 1, 2,
-            // Triangle 1
-            3, 4, 5, 
-            // Triangle 2
-            6, 7, 8, 
-        ];
-        let colors: Vec<f32> = vec![
-            // Triangle 0
-            1.0, 0.0, 0.0, 0.5,
-            1.0, 0.0, 0.0, 0.5,
-            1.0, 0.0, 0.0, 0.5,
-            // Triangle 1
-            0.0, 1.0, 0.0, 0.5,
-            0.0, 1.0, 0.0, 0.5,
-            0.0, 1.0, 0.0, 0.5,
-            // Triangle 2
-            0.0, 0.0, 1.0, 0.5,
-            0.0, 0.0, 1.0, 0.5,
-            0.0, 0.0, 1.0, 0.5,
-        ];
-        let vao_id: u32 = unsafe { setup_triangle_vao(&verticies, &indicies, &colors) };
-
-        // Basic usage of shader helper:
-        // The example code below returns a shader object, which contains the field `.program_id`.
-        // The snippet is not enough to do the assignment, and will need to be modified (outside of
-        // just using the correct path), but it only needs to be called once
-        //
-        //     shader::ShaderBuilder::new()
-        //        .attach_file("./path/to/shader.file")
-        //        .link();
-        let shader_program = unsafe {
-            shader::ShaderBuilder::new()
-                .attach_file("./shaders/simple.frag")
-                .attach_file("./shaders/simple.vert")
-                .link()
+        // Set up openGL and everything the scene needs
+        let mut renderer = unsafe {
+            renderer::setup_gl();
+            Renderer::new(&options, initial_size.width, initial_size.height)
         };
-        unsafe {
-            let screen_dims_name = CString::new("screenDims").expect("Could not allocate c string");
-            let screen_dims_uniform_loc =
-                gl::GetUniformLocation(shader_program.program_id, screen_dims_name.as_ptr());
-            gl::ProgramUniform2ui(
-                shader_program.program_id,
-                screen_dims_uniform_loc,
-                SCREEN_W,
-                SCREEN_H,
-            );
-        }
 
-        // Used to demonstrate keyboard handling -- feel free to remove
-        let mut _arbitrary_number = 0.0;
-        
-        let first_frame_time = std::time::Instant::now();
-        let mut last_frame_time = first_frame_time;
-        
-        // Set up uniforms
-        let time_uniform = shader::ShaderUniform::new(&shader_program, "iTime");
-        let screen_dims_uniform = shader::ShaderUniform::new(&shader_program, "screenDims");
-        let camera_uniform = shader::ShaderUniform::new(&shader_program, "camera");
-        screen_dims_uniform.update2f(&[SCREEN_W as f32, SCREEN_H as f32]);
-        
-        let mut camera = glm::perspective(
-            (SCREEN_W as f32) /(SCREEN_H as f32),
-                120.0,
-                1.0,
-                100.0
-        );
-        camera_uniform.updatefmat4(&camera, false);
-
-        let rot_amount = 0.01;
+        // Keys held during the previous frame, so toggles only fire once per press
+        let mut previous_keys: Vec<VirtualKeyCode> = Vec::new();
+        // Set by F12, and taken once the frame has been drawn
+        let mut take_screenshot = false;
+        // R starts and stops recording, e.g. to make a video for the report. Every take gets a
+        // file or folder of its own under `options.output`
+        let start_recording = || {
+            match Recorder::start(&recording::take_output(&options.output), options.frame_rate) {
+                Ok(recorder) => {
+                    println!("Recording to {}", recorder.output().display());
+                    Some(recorder)
+                }
+                Err(e) => {
+                    println!("WARNING: could not start recording: {}", e);
+                    None
+                }
+            }
+        };
+        let mut recorder = if options.record { start_recording() } else { None };
 
-        let y_axis = glm::vec3::<f32>(0.0, 1.0, 0.0);
-        let x_axis = glm::vec3::<f32>(1.0, 0.0, 0.0);
-        let z_axis = glm::vec3::<f32>(0.0, 0.0, 1.0);
+        let mut last_frame_time = std::time::Instant::now();
+        // Seconds of animation drawn so far
+        let mut time = 0.0;
 
-        let move_amount = 0.01;
+        let fly_controller = FlyController::new(options.mouse_sensitivity);
+        let mut camera_mode = CameraMode::Fly;
 
         // The main rendering loop
         loop {
+            // While recording, time moves in fixed steps instead of following the clock, so the
+            // video plays back smoothly however long each frame took to draw and save
             let now = std::time::Instant::now();
-            let elapsed = now.duration_since(first_frame_time).as_secs_f32();
-            let delta_time = now.duration_since(last_frame_time).as_secs_f32();
+            let delta_time = match recorder {
+                Some(_) => 1.0 / options.frame_rate,
+                None => now.duration_since(last_frame_time).as_secs_f32(),
+            };
             last_frame_time = now;
+            time += delta_time;
 
+            // Handle mouse movement. delta contains the x and y movement of the mouse since last frame in pixels
+            let mouse_movement = match mouse_delta.lock() {
+                Ok(mut delta) => std::mem::replace(&mut *delta, (0.0, 0.0)),
+                Err(_) => (0.0, 0.0),
+            };
+            let scroll = match scroll_delta.lock() {
+                Ok(mut delta) => std::mem::replace(&mut *delta, 0.0),
+                Err(_) => 0.0,
+            };
             // Handle keyboard input
             if let Ok(keys) = pressed_keys.lock() {
                 for key in keys.iter() {
                     match key {
-                        VirtualKeyCode::A => {
-                            _arbitrary_number += delta_time;
-                            camera = glm::rotate::<f32>(&camera, -rot_amount, &y_axis);
+                        VirtualKeyCode::C if !previous_keys.contains(key) => {
+                            camera_mode = match camera_mode {
+                                CameraMode::Fly => {
+                                    let bounds = renderer.frame_bounds;
+                                    let center = (bounds.0 + bounds.1) / 2.0;
+                                    let distance =
+                                        glm::distance(&renderer.camera.position, &center);
+                                    let orbit =
+                                        OrbitController::from_camera(&renderer.camera, distance);
+                                    CameraMode::Orbit(orbit)
+                                }
+                                CameraMode::Orbit(_) => CameraMode::Fly,
+                            };
+                        }
+                        VirtualKeyCode::F if !previous_keys.contains(key) => {
+                            if let CameraMode::Orbit(orbit) = &mut camera_mode {
+                                orbit.frame(&mut renderer.camera, renderer.frame_bounds);
+                            }
+                        }
+                        VirtualKeyCode::L if !previous_keys.contains(key) => {
+                            renderer.next_shading();
+                        }
+                        VirtualKeyCode::P if !previous_keys.contains(key) => {
+                            unsafe { renderer.toggle_particles() };
                         }
-                        VirtualKeyCode::D => {
-                            _arbitrary_number -= delta_time;
-                            camera = glm::rotate::<f32>(&camera, rot_amount, &y_axis);
+                        VirtualKeyCode::I if !previous_keys.contains(key) => {
+                            unsafe { renderer.print_interface() };
                         }
-                        VirtualKeyCode::W => {
-                            camera = glm::rotate::<f32>(&camera, -rot_amount, &x_axis);
+                        VirtualKeyCode::F12 if !previous_keys.contains(key) => {
+                            take_screenshot = true;
                         }
-                        VirtualKeyCode::S => {
-                            camera = glm::rotate::<f32>(&camera, rot_amount, &x_axis);
+                        VirtualKeyCode::R if !previous_keys.contains(key) => {
+                            recorder = match recorder.take() {
+                                None => start_recording(),
+                                Some(recorder) => {
+                                    let output = recorder.output().to_path_buf();
+                                    match recorder.finish() {
+                                        Ok(frames) => println!(
+                                            "Recorded {} frames to {}",
+                                            frames,
+                                            output.display()
+                                        ),
+                                        Err(e) => println!("WARNING: recording failed: {}", e),
+                                    }
+                                    None
+                                }
+                            };
                         }
                         _ => {}
                     }
                 }
-            }
-            // Handle mouse movement. delta contains the x and y movement of the mouse since last frame in pixels
-            if let Ok(mut delta) = mouse_delta.lock() {
-                *delta = (0.0, 0.0);
+                match &mut camera_mode {
+                    CameraMode::Fly => {
+                        let camera = &mut renderer.camera;
+                        fly_controller.update(camera, &keys, mouse_movement, delta_time);
+                    }
+                    CameraMode::Orbit(orbit) => {
+                        if let Ok(buttons) = mouse_buttons.lock() {
+                            orbit.update(&mut renderer.camera, &buttons, mouse_movement, scroll);
+                        }
+                    }
+                }
+                previous_keys = keys.clone();
             }
 
+            // Follow the window size. Minimized windows report a size of zero, which is skipped
+            // until the window is restored
+            let new_size = resized.lock().ok().and_then(|mut size| size.take());
+            if let Some(size) = new_size.filter(|size| size.width > 0 && size.height > 0) {
+                context.resize(size);
+                unsafe { renderer.resize(size.width, size.height) };
+            }
 
-            time_uniform.update1f(first_frame_time.elapsed().as_secs_f32());
-            camera_uniform.updatefmat4(&camera, false);
-
-            unsafe {
-                gl::ClearColor(0.0, 0.0, 0.0, 1.0); // moon raker, full opacity
-                gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+            unsafe { renderer.draw(time) };
 
-                // Issue the necessary commands to draw your scene here
-                gl::BindVertexArray(vao_id);
-                gl::EnableVertexArrayAttrib(vao_id, 0);
-                shader_program.activate();
-                gl::DrawElements(
-                    gl::TRIANGLES,
-                    (&indicies).len() as i32,
-                    gl::UNSIGNED_INT,
-                    0 as *const c_void,
-                );
+            // Read the back buffer before it is swapped away
+            if take_screenshot {
+                let image = unsafe { renderer.read_pixels() };
+                screenshot::save_in_background(image, screenshot::SCREENSHOT_FOLDER);
+                take_screenshot = false;
+            }
+            if let Some(recorder) = &recorder {
+                recorder.record(unsafe { renderer.read_pixels() });
             }
 
             context.swap_buffers().unwrap();
@@ -309,7 +261,7 @@ fn main() {
     let render_thread_healthy = Arc::new(RwLock::new(true));
     let render_thread_watchdog = Arc::clone(&render_thread_healthy);
     thread::spawn(move || {
-        if !render_thread.join().is_ok() {
+        if render_thread.join().is_err() {
             if let Ok(mut health) = render_thread_watchdog.write() {
                 println!("Render thread panicked!");
                 *health = false;
@@ -317,13 +269,17 @@ fn main() {
         }
     });
 
+    // Mouse movement is reported even while another window has focus, so it's only passed on while
+    // this one does
+    let mut focused = true;
+
     // Start the event loop -- This is where window events get handled
     el.run(move |event, _, control_flow| {
         *control_flow = ControlFlow::Wait;
 
         // Terminate program if render thread panics
         if let Ok(health) = render_thread_healthy.read() {
-            if *health == false {
+            if !*health {
                 *control_flow = ControlFlow::Exit;
             }
         }
@@ -335,6 +291,39 @@ fn main() {
             } => {
                 *control_flow = ControlFlow::Exit;
             }
+            // Pass new sizes on to the render thread. Moving to a screen with a different scale
+            // factor changes the size in pixels without necessarily sending a `Resized`
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                if let Ok(mut resized) = arc_resized.lock() {
+                    *resized = Some(size);
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::ScaleFactorChanged { new_inner_size, .. },
+                ..
+            } => {
+                if let Ok(mut resized) = arc_resized.lock() {
+                    *resized = Some(*new_inner_size);
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::Focused(is_focused),
+                ..
+            } => {
+                focused = is_focused;
+                // Keys released while unfocused never send a release event, so they would be stuck
+                if !focused {
+                    if let Ok(mut keys) = arc_pressed_keys.lock() {
+                        keys.clear();
+                    }
+                    if let Ok(mut buttons) = arc_mouse_buttons.lock() {
+                        buttons.clear();
+                    }
+                }
+            }
             // Keep track of currently pressed keys to send to the rendering thread
             Event::WindowEvent {
                 event:
@@ -376,10 +365,39 @@ fn main() {
                     _ => {}
                 }
             }
+            // Keep track of currently pressed mouse buttons to send to the rendering thread
+            Event::WindowEvent {
+                event: WindowEvent::MouseInput { state, button, .. },
+                ..
+            } => {
+                if let Ok(mut buttons) = arc_mouse_buttons.lock() {
+                    match state {
+                        Released => buttons.retain(|&b| b != button),
+                        Pressed => {
+                            if !buttons.contains(&button) {
+                                buttons.push(button);
+                            }
+                        }
+                    }
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::MouseWheel { delta, .. },
+                ..
+            } => {
+                // Touchpads report pixels rather than lines
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+                };
+                if let Ok(mut scroll) = arc_scroll_delta.lock() {
+                    *scroll += lines;
+                }
+            }
             Event::DeviceEvent {
                 event: DeviceEvent::MouseMotion { delta },
                 ..
-            } => {
+            } if focused => {
                 // Accumulate mouse movement
                 if let Ok(mut position) = arc_mouse_delta.lock() {
                     *position = (position.0 + delta.0 as f32, position.1 + delta.1 as f32);
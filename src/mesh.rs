@@ -0,0 +1,340 @@
+// Triangle meshes on the CPU, loading them from Wavefront OBJ files, and uploading them to the GPU.
+//
+// Every mesh uses the same vertex format, so any mesh can be drawn with any program that reads
+// positions, normals and texture coordinates from the locations below.
+
+use std::{
+    fmt, mem,
+    os::raw::c_void,
+    path::{Path, PathBuf},
+};
+
+use crate::gl_object::VertexArray;
+use crate::vertex::{Vertex, VertexArrayBuilder, VertexAttribute, VertexLayout};
+
+pub const POSITION_LOCATION: u32 = 0;
+pub const NORMAL_LOCATION: u32 = 2;
+pub const UV_LOCATION: u32 = 3;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MeshVertex {
+    pub position: glm::Vec3,
+    pub normal: glm::Vec3,
+    pub uv: glm::Vec2,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub name: String,
+    pub vertices: Vec<MeshVertex>,
+    // Three per triangle
+    pub indices: Vec<u32>,
+    // Index into the materials loaded alongside the mesh
+    pub material: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    pub diffuse: glm::Vec3,
+    // 1 is opaque, 0 fully transparent
+    pub opacity: f32,
+    // Resolved relative to the file the material came from
+    pub diffuse_texture: Option<PathBuf>,
+}
+
+// Everything loaded from an OBJ file
+pub struct ObjFile {
+    pub meshes: Vec<Mesh>,
+    // A missing or broken MTL file doesn't stop the meshes from loading, so its error is kept
+    // for the caller to report
+    pub materials: Result<Vec<Material>, MeshError>,
+}
+
+// A mesh that has been uploaded to the GPU
+pub struct GpuMesh {
+    pub vao: VertexArray,
+    pub index_count: i32,
+}
+
+#[derive(Debug)]
+pub enum MeshError {
+    Obj { path: PathBuf, source: tobj::LoadError },
+    // The MTL files an OBJ file refers to couldn't be loaded
+    Mtl { path: PathBuf, source: tobj::LoadError },
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MeshError::Obj { path, source } => {
+                write!(f, "failed to load {}: {}", path.display(), source)
+            }
+            MeshError::Mtl { path, source } => {
+                write!(f, "failed to load the materials of {}: {}", path.display(), source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MeshError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MeshError::Obj { source, .. } | MeshError::Mtl { source, .. } => Some(source),
+        }
+    }
+}
+
+impl Vertex for MeshVertex {
+    fn layout() -> VertexLayout {
+        VertexLayout::of::<MeshVertex>()
+            .attribute(
+                VertexAttribute::new::<f32>(POSITION_LOCATION, 3)
+                    .offset(mem::offset_of!(MeshVertex, position)),
+            )
+            .attribute(
+                VertexAttribute::new::<f32>(NORMAL_LOCATION, 3)
+                    .offset(mem::offset_of!(MeshVertex, normal)),
+            )
+            .attribute(
+                VertexAttribute::new::<f32>(UV_LOCATION, 2)
+                    .offset(mem::offset_of!(MeshVertex, uv)),
+            )
+    }
+}
+
+impl Mesh {
+    // Smooth normals, averaged from the faces around each vertex weighted by their area
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![glm::Vec3::zeros(); self.vertices.len()];
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| i as usize);
+            let (pa, pb, pc) = (
+                self.vertices[a].position,
+                self.vertices[b].position,
+                self.vertices[c].position,
+            );
+            // Not normalized, so larger faces count for more
+            let face_normal = glm::cross(&(pb - pa), &(pc - pa));
+            for &i in &[a, b, c] {
+                normals[i] += face_normal;
+            }
+        }
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            vertex.normal = if normal.norm_squared() > 0.0 {
+                normal.normalize()
+            } else {
+                glm::vec3(0.0, 1.0, 0.0)
+            };
+        }
+    }
+
+    // The corners of the smallest axis aligned box containing every vertex
+    pub fn bounds(&self) -> Option<(glm::Vec3, glm::Vec3)> {
+        let first = self.vertices.first()?.position;
+        Some(self.vertices.iter().fold((first, first), |(min, max), v| {
+            (glm::min2(&min, &v.position), glm::max2(&max, &v.position))
+        }))
+    }
+
+    pub unsafe fn upload(&self) -> GpuMesh {
+        let vao = VertexArrayBuilder::new()
+            .vertices(&self.vertices)
+            .indices(&self.indices)
+            .build();
+        GpuMesh {
+            vao,
+            index_count: self.indices.len() as i32,
+        }
+    }
+}
+
+impl GpuMesh {
+    pub unsafe fn draw(&self) {
+        self.vao.bind();
+        gl::DrawElements(
+            gl::TRIANGLES,
+            self.index_count,
+            gl::UNSIGNED_INT,
+            std::ptr::null::<c_void>(),
+        );
+    }
+}
+
+// The axis aligned box around `bounds` once it has been transformed
+pub fn transform_bounds(
+    (min, max): (glm::Vec3, glm::Vec3),
+    transform: &glm::Mat4,
+) -> (glm::Vec3, glm::Vec3) {
+    let corner = |i: usize| {
+        let pick = |axis: usize| if i & (1 << axis) == 0 { min[axis] } else { max[axis] };
+        (transform * glm::vec4(pick(0), pick(1), pick(2), 1.0)).xyz()
+    };
+    (1..8).map(corner).fold((corner(0), corner(0)), |(min, max), p| {
+        (glm::min2(&min, &p), glm::max2(&max, &p))
+    })
+}
+
+// The smallest box containing both boxes
+pub fn union_bounds(
+    a: (glm::Vec3, glm::Vec3),
+    b: (glm::Vec3, glm::Vec3),
+) -> (glm::Vec3, glm::Vec3) {
+    (glm::min2(&a.0, &b.0), glm::max2(&a.1, &b.1))
+}
+
+fn texture_path(dir: &Path, name: &str) -> Option<PathBuf> {
+    if name.is_empty() {
+        None
+    } else {
+        Some(dir.join(name))
+    }
+}
+
+impl Material {
+    fn from_obj(material: tobj::Material, dir: &Path) -> Material {
+        Material {
+            diffuse: material.diffuse.into(),
+            opacity: material.dissolve,
+            diffuse_texture: texture_path(dir, &material.diffuse_texture),
+            name: material.name,
+        }
+    }
+}
+
+fn mesh_from_obj(model: tobj::Model) -> Mesh {
+    let obj = model.mesh;
+    let vertex_count = obj.positions.len() / 3;
+    let has_normals = obj.normals.len() == obj.positions.len();
+    let has_uvs = obj.texcoords.len() / 2 == vertex_count;
+
+    let vertices = (0..vertex_count)
+        .map(|i| MeshVertex {
+            position: glm::vec3(obj.positions[3 * i], obj.positions[3 * i + 1], obj.positions[3 * i + 2]),
+            normal: if has_normals {
+                glm::vec3(obj.normals[3 * i], obj.normals[3 * i + 1], obj.normals[3 * i + 2])
+            } else {
+                glm::Vec3::zeros()
+            },
+            uv: if has_uvs {
+                glm::vec2(obj.texcoords[2 * i], obj.texcoords[2 * i + 1])
+            } else {
+                glm::Vec2::zeros()
+            },
+        })
+        .collect();
+
+    let mut mesh = Mesh {
+        name: model.name,
+        vertices,
+        indices: obj.indices,
+        material: obj.material_id,
+    };
+    if !has_normals {
+        mesh.compute_normals();
+    }
+    mesh
+}
+
+// Loads every object in an OBJ file, along with the materials of its MTL files. Faces are
+// triangulated, and positions, normals and texture coordinates share a single index buffer
+pub fn load_obj(path: &str) -> Result<ObjFile, MeshError> {
+    let path = Path::new(path);
+    let options = tobj::LoadOptions {
+        single_index: true,
+        triangulate: true,
+        ignore_points: true,
+        ignore_lines: true,
+    };
+    let (models, materials) = tobj::load_obj(path, &options).map_err(|source| MeshError::Obj {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let materials = materials
+        .map(|materials| {
+            materials
+                .into_iter()
+                .map(|m| Material::from_obj(m, dir))
+                .collect()
+        })
+        .map_err(|source| MeshError::Mtl {
+            path: path.to_path_buf(),
+            source,
+        });
+    let meshes = models.into_iter().map(mesh_from_obj).collect();
+
+    Ok(ObjFile { meshes, materials })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quads_are_triangulated_and_given_normals() {
+        let ObjFile { meshes, materials } = load_obj("tests/scenes/quad.obj").unwrap();
+        assert!(materials.unwrap().is_empty());
+        assert_eq!(meshes.len(), 1);
+        let quad = &meshes[0];
+        assert_eq!(quad.vertices.len(), 4);
+        assert_eq!(quad.indices.len(), 6);
+        // The file has no normals, so they come from the faces
+        for vertex in &quad.vertices {
+            assert_eq!(vertex.normal, glm::vec3(0.0, 0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn bounds_follow_transforms() {
+        let ObjFile { meshes, .. } = load_obj("tests/scenes/quad.obj").unwrap();
+        let bounds = meshes[0].bounds().unwrap();
+        assert_eq!(bounds, (glm::vec3(0.0, 0.0, 0.0), glm::vec3(1.0, 1.0, 0.0)));
+
+        // A quarter turn around Z swings the quad over to negative X
+        let turn = glm::rotation(std::f32::consts::FRAC_PI_2, &glm::vec3(0.0, 0.0, 1.0));
+        let transform = glm::translation(&glm::vec3(0.0, 0.0, 2.0)) * turn;
+        let (min, max) = transform_bounds(bounds, &transform);
+        assert!(glm::distance(&min, &glm::vec3(-1.0, 0.0, 2.0)) < 1e-6);
+        assert!(glm::distance(&max, &glm::vec3(0.0, 1.0, 2.0)) < 1e-6);
+
+        let unit = (glm::vec3(0.0, 0.0, 0.0), glm::vec3(1.0, 1.0, 1.0));
+        let apart = (glm::vec3(2.0, -1.0, 0.5), glm::vec3(3.0, 0.0, 0.5));
+        assert_eq!(
+            union_bounds(unit, apart),
+            (glm::vec3(0.0, -1.0, 0.0), glm::vec3(3.0, 1.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn materials_and_their_textures() {
+        let ObjFile { meshes, materials } = load_obj("tests/scenes/textured/triangle.obj").unwrap();
+        let materials = materials.unwrap();
+        assert_eq!(materials.len(), 1);
+        let painted = &materials[0];
+        assert_eq!(painted.name, "painted");
+        assert_eq!(painted.diffuse, glm::vec3(1.0, 0.0, 0.0));
+        assert_eq!(painted.opacity, 0.5);
+        // Relative to the directory of the OBJ file rather than the working directory
+        assert_eq!(
+            painted.diffuse_texture.as_deref(),
+            Some(Path::new("tests/scenes/textured/textures/paint.png"))
+        );
+
+        let triangle = &meshes[0];
+        assert_eq!(triangle.material, Some(0));
+        assert_eq!(triangle.vertices[1].uv, glm::vec2(1.0, 0.0));
+        assert_eq!(triangle.vertices[1].normal, glm::vec3(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn missing_mtl_still_loads_meshes() {
+        let ObjFile { meshes, materials } = load_obj("tests/scenes/missing_mtl.obj").unwrap();
+        assert!(matches!(materials, Err(MeshError::Mtl { .. })));
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].material, None);
+        assert_eq!(meshes[0].indices.len(), 3);
+    }
+}
@@ -0,0 +1,99 @@
+// Command line options, e.g. `cargo run -- model.gltf --skybox sky.hdr --grab-cursor`,
+// `cargo run -- model.gltf --headless --frames 10 --output frames` to render without a window, or
+// `cargo run -- model.gltf --record --output video.y4m` to record the window
+
+use std::str::FromStr;
+
+pub struct Options {
+    // An OBJ or glTF file to draw
+    pub model: Option<String>,
+    // Either an equirectangular image, or a folder holding the six faces of a cubemap named
+    // px, nx, py, ny, pz and nz
+    pub skybox: Option<String>,
+    // Keeps the mouse inside the window and hides it
+    pub grab_cursor: bool,
+    // Radians the camera turns per pixel of mouse movement
+    pub mouse_sensitivity: f32,
+    // Render offscreen to image files instead of opening a window
+    pub headless: bool,
+    // How many frames to render headless
+    pub frames: u32,
+    // Frames per second of simulated time when rendering headless or recording
+    pub frame_rate: f32,
+    // Seconds of simulated time at which the first headless frame is drawn
+    pub start_time: f32,
+    // Record the window from the start, rather than from when R is pressed
+    pub record: bool,
+    // Where headless and recorded frames are written: a Y4M video if it ends in .y4m, or else a
+    // folder of numbered PNG files. Each recording of the window is a timestamped take next to the
+    // video or inside the folder
+    pub output: String,
+    // Width and height in pixels of headless frames
+    pub size: (u32, u32),
+}
+
+// Reads the value following an option, warning when it is missing or malformed
+fn value<T: FromStr>(option: &str, value: Option<String>, expected: &str) -> Option<T> {
+    let parsed = value.as_deref().and_then(|value| value.parse().ok());
+    if parsed.is_none() {
+        println!("WARNING: {} expects {}", option, expected);
+    }
+    parsed
+}
+
+fn parse_size(size: &str) -> Option<(u32, u32)> {
+    let (width, height) = size.split_once('x')?;
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+impl Options {
+    pub fn from_args() -> Options {
+        let mut options = Options {
+            model: None,
+            skybox: None,
+            grab_cursor: false,
+            mouse_sensitivity: 0.002,
+            headless: false,
+            frames: 1,
+            frame_rate: 60.0,
+            start_time: 0.0,
+            record: false,
+            output: "./output".to_string(),
+            size: (800, 600),
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--skybox" => options.skybox = args.next(),
+                "--grab-cursor" => options.grab_cursor = true,
+                "--sensitivity" => {
+                    let sensitivity = value(&arg, args.next(), "a number");
+                    options.mouse_sensitivity = sensitivity.unwrap_or(options.mouse_sensitivity);
+                }
+                "--headless" => options.headless = true,
+                "--frames" => {
+                    let frames = value(&arg, args.next(), "a whole number");
+                    options.frames = frames.unwrap_or(options.frames);
+                }
+                "--frame-rate" => {
+                    let frame_rate = value(&arg, args.next(), "a positive number");
+                    let frame_rate = frame_rate.filter(|&rate: &f32| rate > 0.0);
+                    options.frame_rate = frame_rate.unwrap_or(options.frame_rate);
+                }
+                "--time" => {
+                    let start_time = value(&arg, args.next(), "a number");
+                    options.start_time = start_time.unwrap_or(options.start_time);
+                }
+                "--record" => options.record = true,
+                "--output" => options.output = args.next().unwrap_or(options.output),
+                "--size" => match args.next().as_deref().and_then(parse_size) {
+                    Some(size) if size.0 > 0 && size.1 > 0 => options.size = size,
+                    _ => println!("WARNING: --size expects WIDTHxHEIGHT, e.g. 800x600"),
+                },
+                _ if arg.starts_with("--") => println!("WARNING: unknown option {}", arg),
+                _ => options.model = Some(arg),
+            }
+        }
+        options
+    }
+}
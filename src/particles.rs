@@ -0,0 +1,114 @@
+// A fountain of particles that is simulated entirely on the GPU. A compute program moves the
+// particles in a storage buffer, which the draw program then reads directly as points, so the
+// particles never pass through the CPU after they have been created.
+
+use crate::gl_object::VertexArray;
+use crate::shader::block::UniformBlock;
+use crate::shader::compute::{memory_barrier, MemoryBarrier, StorageBuffer};
+use crate::shader::{ShaderBuilder, ShaderError, ShaderUniform, WatchedShader};
+use crate::renderer::{FrameBlock, FRAME_BLOCK};
+
+const PARTICLE_COUNT: usize = 4096;
+
+// The binding point of the `Particles` storage block, and its name in particles.glsl
+const PARTICLES_BINDING: u32 = 0;
+const PARTICLES_BLOCK: &str = "Particles";
+
+// Has to match `Particle` in particles.glsl, which lays it out by the std430 rules
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct Particle {
+    // The w component is the number of seconds left before the particle respawns
+    position: glm::Vec4,
+    // The w component is unused
+    velocity: glm::Vec4,
+}
+
+pub struct Particles {
+    update_program: WatchedShader,
+    draw_program: WatchedShader,
+    particles: StorageBuffer<Particle>,
+    delta_time_uniform: ShaderUniform,
+    origin_uniform: ShaderUniform,
+    // Where the particles respawn
+    origin: glm::Vec3,
+    // Points are drawn without attributes, but core profile still needs a vertex array bound
+    vao: VertexArray,
+}
+
+// Every particle is due to respawn, so the first step sends them all off from the origin
+fn spawning() -> Vec<Particle> {
+    let particle = Particle {
+        position: glm::vec4(0.0, 0.0, 0.0, 0.0),
+        velocity: glm::vec4(0.0, 0.0, 0.0, 0.0),
+    };
+    vec![particle; PARTICLE_COUNT]
+}
+
+impl Particles {
+    pub unsafe fn new(
+        frame_block: &UniformBlock<FrameBlock>,
+        origin: glm::Vec3,
+    ) -> Result<Particles, ShaderError> {
+        let update_program = ShaderBuilder::new()
+            .include_dir("./shaders/include")
+            .attach_file("./shaders/particles.comp")
+            .and_then(|b| b.link_watched())?;
+        let draw_program = ShaderBuilder::new()
+            .include_dir("./shaders/include")
+            .attach_file("./shaders/particles.frag")
+            .and_then(|b| b.attach_file("./shaders/particles.vert"))
+            .and_then(|b| b.link_watched())?;
+
+        let particles = Particles {
+            particles: StorageBuffer::new(PARTICLES_BINDING, &spawning()),
+            delta_time_uniform: ShaderUniform::new(&update_program, "deltaTime"),
+            origin_uniform: ShaderUniform::new(&update_program, "origin"),
+            update_program,
+            draw_program,
+            origin,
+            vao: VertexArray::new(),
+        };
+        particles.bind(frame_block);
+        particles.origin_uniform.set(&particles.origin);
+        Ok(particles)
+    }
+
+    unsafe fn bind(&self, frame_block: &UniformBlock<FrameBlock>) {
+        self.particles.bind_to(&self.update_program, PARTICLES_BLOCK);
+        self.particles.bind_to(&self.draw_program, PARTICLES_BLOCK);
+        frame_block.bind_to(&self.draw_program, FRAME_BLOCK);
+    }
+
+    // Sends every particle off from the origin again
+    pub unsafe fn restart(&self) {
+        self.particles.update(0, &spawning());
+    }
+
+    // The uniforms and the block bindings belong to the old programs, so they have to be set up
+    // again
+    pub unsafe fn reload_if_changed(&mut self, frame_block: &UniformBlock<FrameBlock>) {
+        let update_reloaded = self.update_program.reload_if_changed();
+        let draw_reloaded = self.draw_program.reload_if_changed();
+        if update_reloaded {
+            self.delta_time_uniform.refresh(&self.update_program);
+            self.origin_uniform.refresh(&self.update_program);
+            self.origin_uniform.set(&self.origin);
+        }
+        if update_reloaded || draw_reloaded {
+            self.bind(frame_block);
+        }
+    }
+
+    // Moves the particles `delta_time` seconds forward and draws them
+    pub unsafe fn step_and_draw(&self, delta_time: f32) {
+        self.delta_time_uniform.set(&delta_time);
+        self.update_program.dispatch_invocations(self.particles.len() as u32, 1, 1);
+        // The draw reads the positions the dispatch wrote
+        memory_barrier(MemoryBarrier::SHADER_STORAGE);
+
+        self.draw_program.activate();
+        self.vao.bind();
+        gl::DrawArrays(gl::POINTS, 0, self.particles.len() as i32);
+    }
+}
@@ -0,0 +1,245 @@
+// Writing a sequence of frames to disk, either as numbered PNG files or as a raw YUV4MPEG2 (Y4M)
+// video, which e.g. `ffmpeg -i recording.y4m recording.mp4` turns into something smaller.
+//
+// Frames are encoded on a thread of their own so rendering doesn't wait on it. A few frames may be
+// queued up, after which `record` blocks until the writer catches up, so a slow disk slows
+// rendering down rather than filling up memory.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use image::RgbaImage;
+
+use crate::util;
+
+const QUEUED_FRAMES: usize = 8;
+
+pub struct Recorder {
+    output: PathBuf,
+    frames: Option<SyncSender<RgbaImage>>,
+    // Returns how many frames were written
+    writer: Option<JoinHandle<Result<u32, String>>>,
+}
+
+// Saves each frame as frame_0000.png, frame_0001.png and so on
+fn write_png_sequence(folder: PathBuf, frames: Receiver<RgbaImage>) -> Result<u32, String> {
+    let mut count = 0;
+    for frame in frames {
+        let path = folder.join(format!("frame_{:04}.png", count));
+        frame
+            .save(&path)
+            .map_err(|e| format!("could not write {}: {}", path.display(), e))?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+// The frame rate as a fraction, which is how Y4M headers store it
+fn frame_rate_ratio(frame_rate: f32) -> (u32, u32) {
+    let (mut numerator, mut denominator) = ((frame_rate * 1000.0).round() as u32, 1000);
+    let (mut a, mut b) = (numerator, denominator);
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    if a > 1 {
+        numerator /= a;
+        denominator /= a;
+    }
+    (numerator, denominator)
+}
+
+// Converts a frame to 8 bit limited range BT.601 YUV with 4:2:0 chroma subsampling, which is what
+// players assume Y4M holds. Each chroma sample is the average of the up to 2x2 pixels it covers
+fn write_y4m_frame(out: &mut impl Write, frame: &RgbaImage) -> std::io::Result<()> {
+    let (width, height) = frame.dimensions();
+    let rgb = |x: u32, y: u32| {
+        let [r, g, b, _] = frame.get_pixel(x, y).0;
+        [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0]
+    };
+
+    let mut luma = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let [r, g, b] = rgb(x, y);
+            luma.push((16.0 + 65.481 * r + 128.553 * g + 24.966 * b).round() as u8);
+        }
+    }
+
+    let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+    let mut blue = Vec::with_capacity((chroma_width * chroma_height) as usize);
+    let mut red = Vec::with_capacity(blue.capacity());
+    for y in 0..chroma_height {
+        for x in 0..chroma_width {
+            let covered: Vec<[f32; 3]> = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                .iter()
+                .map(|(dx, dy)| (2 * x + dx, 2 * y + dy))
+                .filter(|&(x, y)| x < width && y < height)
+                .map(|(x, y)| rgb(x, y))
+                .collect();
+            let average = |channel: usize| {
+                covered.iter().map(|color| color[channel]).sum::<f32>() / covered.len() as f32
+            };
+            let (r, g, b) = (average(0), average(1), average(2));
+            blue.push((128.0 - 37.797 * r - 74.203 * g + 112.0 * b).round() as u8);
+            red.push((128.0 + 112.0 * r - 93.786 * g - 18.214 * b).round() as u8);
+        }
+    }
+
+    out.write_all(b"FRAME\n")?;
+    out.write_all(&luma)?;
+    out.write_all(&blue)?;
+    out.write_all(&red)?;
+    // Flushed every frame, so quitting mid-recording loses at most the frame being written
+    out.flush()
+}
+
+// Writes every frame to a single Y4M stream. The size of the video is that of the first frame, and
+// frames of any other size are skipped
+fn write_y4m(
+    file: File,
+    path: PathBuf,
+    frame_rate: f32,
+    frames: Receiver<RgbaImage>,
+) -> Result<u32, String> {
+    let error = |e: std::io::Error| format!("could not write {}: {}", path.display(), e);
+    let mut out = BufWriter::new(file);
+    let mut size = None;
+    let mut count = 0;
+    let mut warned = false;
+    for frame in frames {
+        match size {
+            None => {
+                let (numerator, denominator) = frame_rate_ratio(frame_rate);
+                let (width, height) = frame.dimensions();
+                writeln!(
+                    out,
+                    "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C420jpeg",
+                    width, height, numerator, denominator
+                )
+                .map_err(error)?;
+                size = Some(frame.dimensions());
+            }
+            Some((width, height)) if (width, height) != frame.dimensions() => {
+                if !warned {
+                    println!(
+                        "WARNING: {} is {}x{}, frames of other sizes are left out",
+                        path.display(),
+                        width,
+                        height
+                    );
+                    warned = true;
+                }
+                continue;
+            }
+            Some(_) => {}
+        }
+        write_y4m_frame(&mut out, &frame).map_err(error)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+// Where a new take goes when recording to `output` from the window, so takes never overwrite or mix
+// with earlier ones: e.g. video_<timestamp>.y4m next to video.y4m, or else a take_<timestamp>
+// folder inside the folder `output`
+pub fn take_output(output: &str) -> String {
+    let path = Path::new(output);
+    let take = if path.extension() == Some("y4m".as_ref()) {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        path.with_file_name(format!("{}_{}.y4m", stem, util::timestamp()))
+    } else {
+        path.join(format!("take_{}", util::timestamp()))
+    };
+    take.to_string_lossy().into_owned()
+}
+
+impl Recorder {
+    // Records to a Y4M file if `output` ends in .y4m, or else to numbered PNG files in the folder
+    // `output`. `frame_rate` is what the video plays back at, and should match the time step
+    // between the recorded frames
+    pub fn start(output: &str, frame_rate: f32) -> Result<Recorder, String> {
+        let path = Path::new(output).to_path_buf();
+        let (sender, frames) = mpsc::sync_channel(QUEUED_FRAMES);
+
+        let output = path.clone();
+        let writer = if path.extension() == Some("y4m".as_ref()) {
+            if let Some(folder) = path.parent() {
+                std::fs::create_dir_all(folder)
+                    .map_err(|e| format!("could not create {}: {}", folder.display(), e))?;
+            }
+            let file = File::create(&path)
+                .map_err(|e| format!("could not create {}: {}", path.display(), e))?;
+            thread::spawn(move || write_y4m(file, path, frame_rate, frames))
+        } else {
+            std::fs::create_dir_all(&path)
+                .map_err(|e| format!("could not create {}: {}", path.display(), e))?;
+            thread::spawn(move || write_png_sequence(path, frames))
+        };
+
+        Ok(Recorder {
+            output,
+            frames: Some(sender),
+            writer: Some(writer),
+        })
+    }
+
+    // The file or folder the frames are written to
+    pub fn output(&self) -> &Path {
+        &self.output
+    }
+
+    // Queues a frame to be written. If writing has failed, the frame is dropped and the error is
+    // returned by `finish`
+    pub fn record(&self, frame: RgbaImage) {
+        if let Some(frames) = &self.frames {
+            // Sending only fails once the writer has given up
+            let _ = frames.send(frame);
+        }
+    }
+
+    // Waits for the queued frames to be written, and returns how many frames were recorded
+    pub fn finish(mut self) -> Result<u32, String> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> Result<u32, String> {
+        // Closing the channel ends the writer's loop once it has drained the queue
+        self.frames = None;
+        match self.writer.take() {
+            Some(writer) => writer
+                .join()
+                .unwrap_or_else(|_| Err("the recording thread panicked".to_string())),
+            None => Ok(0),
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop() {
+            println!("WARNING: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_get_their_own_output() {
+        let name = |path: &str| Path::new(path).file_name().unwrap().to_string_lossy().into_owned();
+
+        let video = take_output("recordings/video.y4m");
+        assert_eq!(Path::new(&video).parent(), Some(Path::new("recordings")));
+        assert!(name(&video).starts_with("video_2"), "{}", video);
+        assert!(video.ends_with(".y4m"), "{}", video);
+
+        let frames = take_output("output");
+        assert_eq!(Path::new(&frames).parent(), Some(Path::new("output")));
+        assert!(name(&frames).starts_with("take_2"), "{}", frames);
+    }
+}
@@ -0,0 +1,96 @@
+// Offscreen framebuffers to render into instead of the window, and reading their pixels back.
+
+use std::os::raw::c_void;
+
+use gl::types::GLenum;
+
+use crate::gl_object::{Framebuffer, Texture};
+
+// A framebuffer with an 8 bit sRGB color texture and a depth texture of the same size. Like an
+// sRGB window, it stores colors gamma encoded, so what is read back can be saved as is
+pub struct RenderTarget {
+    framebuffer: Framebuffer,
+    // Only read through the framebuffer, but they have to live as long as it does
+    _color: Texture,
+    _depth: Texture,
+    width: u32,
+    height: u32,
+}
+
+unsafe fn attachment(format: GLenum, width: u32, height: u32) -> Texture {
+    let texture = Texture::new(gl::TEXTURE_2D);
+    gl::TextureStorage2D(texture.id(), 1, format, width as i32, height as i32);
+    gl::TextureParameteri(texture.id(), gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+    gl::TextureParameteri(texture.id(), gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+    texture
+}
+
+// Reads the color buffer of a framebuffer, 0 being the window. Rows are flipped, since OpenGL
+// returns the bottom row first while images start at the top.
+//
+// Alpha is set to opaque. What ends up in the alpha channel depends on the blended draws and on
+// whether the framebuffer has one at all, and nothing on screen shows it, but image viewers would
+// show it as see-through
+pub unsafe fn read_pixels(framebuffer: u32, width: u32, height: u32) -> image::RgbaImage {
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    let mut previous = 0;
+    gl::GetIntegerv(gl::READ_FRAMEBUFFER_BINDING, &mut previous);
+    gl::BindFramebuffer(gl::READ_FRAMEBUFFER, framebuffer);
+    gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+    gl::ReadPixels(
+        0,
+        0,
+        width as i32,
+        height as i32,
+        gl::RGBA,
+        gl::UNSIGNED_BYTE,
+        pixels.as_mut_ptr() as *mut c_void,
+    );
+    gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+    gl::BindFramebuffer(gl::READ_FRAMEBUFFER, previous as u32);
+
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel[3] = u8::MAX;
+    }
+    let image = image::RgbaImage::from_raw(width, height, pixels).unwrap();
+    image::imageops::flip_vertical(&image)
+}
+
+impl RenderTarget {
+    pub unsafe fn new(width: u32, height: u32) -> RenderTarget {
+        let framebuffer = Framebuffer::new();
+        let color = attachment(gl::SRGB8_ALPHA8, width, height);
+        let depth = attachment(gl::DEPTH_COMPONENT24, width, height);
+        gl::NamedFramebufferTexture(framebuffer.id(), gl::COLOR_ATTACHMENT0, color.id(), 0);
+        gl::NamedFramebufferTexture(framebuffer.id(), gl::DEPTH_ATTACHMENT, depth.id(), 0);
+
+        let status = gl::CheckNamedFramebufferStatus(framebuffer.id(), gl::FRAMEBUFFER);
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            println!("WARNING: render target is incomplete, status 0x{:x}", status);
+        }
+        RenderTarget {
+            framebuffer,
+            _color: color,
+            _depth: depth,
+            width,
+            height,
+        }
+    }
+
+    // Texture storage can't change size, so this replaces the attachments
+    pub unsafe fn resize(&mut self, width: u32, height: u32) {
+        if (width, height) != (self.width, self.height) {
+            *self = RenderTarget::new(width, height);
+        }
+    }
+
+    // Directs drawing into the target, with the viewport covering all of it
+    pub unsafe fn bind(&self) {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer.id());
+        gl::Viewport(0, 0, self.width as i32, self.height as i32);
+    }
+
+    pub unsafe fn read_pixels(&self) -> image::RgbaImage {
+        read_pixels(self.framebuffer.id(), self.width, self.height)
+    }
+}
@@ -0,0 +1,721 @@
+// The scene and everything needed to draw it.
+//
+// Setting up and drawing lives here rather than in the render loop, so the window and the
+// headless mode run the same code and draw the same images. A `Renderer` draws into its offscreen
+// `RenderTarget` if it has one, or else into the window, and expects the GL context it was created
+// in to be current.
+
+use std::collections::HashMap;
+use std::ptr;
+use std::rc::Rc;
+
+use crate::camera::Camera;
+use crate::gl_object::VertexArray;
+use crate::mesh;
+use crate::options::Options;
+use crate::particles::Particles;
+use crate::render_target::{self, RenderTarget};
+use crate::scene;
+use crate::shader::block::UniformBlock;
+use crate::shader::uniform::TextureUnit;
+use crate::shader::{self, Shader, ShaderPermutations, ShaderUniform, WatchedShader};
+use crate::skybox::Skybox;
+use crate::std140_block;
+use crate::texture::{ColorSpace, Cubemap, Texture2D, TextureError};
+use crate::util;
+use crate::vertex::{VertexArrayBuilder, VertexAttribute, VertexLayout};
+
+// The ways the scene can be shaded, cycled through with L. Each one is a permutation of the
+// same shader sources, selected with preprocessor defines
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Shading {
+    // Plain vertex colors
+    Unlit,
+    // Vertex colors lit by a directional light
+    Lit,
+    // The depth of each fragment in grayscale
+    Depth,
+}
+
+impl Shading {
+    const ALL: [Shading; 3] = [Shading::Unlit, Shading::Lit, Shading::Depth];
+
+    fn defines(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Shading::Unlit => &[],
+            Shading::Lit => &[("LIT", "1")],
+            Shading::Depth => &[("DEBUG_DEPTH", "1")],
+        }
+    }
+
+    // The shading after this one, wrapping around
+    fn next(self) -> Shading {
+        let index = Shading::ALL.iter().position(|&shading| shading == self).unwrap();
+        Shading::ALL[(index + 1) % Shading::ALL.len()]
+    }
+}
+
+// The binding point of the `Frame` block, and its name in common.glsl
+const FRAME_BINDING: u32 = 0;
+pub const FRAME_BLOCK: &str = "Frame";
+
+std140_block! {
+    // Everything a frame's shaders share: the camera, the light and the time. Has to match the
+    // `Frame` block in common.glsl
+    pub struct FrameBlock {
+        view: glm::Mat4,
+        projection: glm::Mat4,
+        // Points towards the light, in world space
+        light_direction: glm::Vec3,
+        ambient: f32,
+        light_color: glm::Vec3,
+        time: f32,
+        screen_dims: glm::Vec2,
+    }
+}
+
+// The uniforms of one shader variant that aren't shared through the `Frame` block. Every variant
+// is its own program, so each needs its own locations
+struct Uniforms {
+    model: ShaderUniform,
+}
+
+impl Uniforms {
+    fn new(program: &Shader) -> Uniforms {
+        Uniforms {
+            model: ShaderUniform::new(program, "model"),
+        }
+    }
+
+    // Looks every uniform up again in `program`, after it has been reloaded
+    fn refresh(&mut self, program: &Shader) {
+        self.model.refresh(program);
+    }
+}
+
+// The uniforms of the model program. Materials and transforms change between draws, so
+// these are set once per mesh
+struct MeshUniforms {
+    model: ShaderUniform,
+    base_color: ShaderUniform,
+    metallic: ShaderUniform,
+    roughness: ShaderUniform,
+    emissive: ShaderUniform,
+    alpha_cutoff: ShaderUniform,
+    base_color_texture: ShaderUniform,
+    metallic_roughness_texture: ShaderUniform,
+}
+
+impl MeshUniforms {
+    fn new(program: &Shader) -> MeshUniforms {
+        MeshUniforms {
+            model: ShaderUniform::new(program, "model"),
+            base_color: ShaderUniform::new(program, "baseColor"),
+            metallic: ShaderUniform::new(program, "metallic"),
+            roughness: ShaderUniform::new(program, "roughness"),
+            emissive: ShaderUniform::new(program, "emissive"),
+            alpha_cutoff: ShaderUniform::new(program, "alphaCutoff"),
+            base_color_texture: ShaderUniform::new(program, "baseColorTexture"),
+            metallic_roughness_texture: ShaderUniform::new(
+                program,
+                "metallicRoughnessTexture",
+            ),
+        }
+    }
+
+    fn refresh(&mut self, program: &Shader) {
+        self.model.refresh(program);
+        self.base_color.refresh(program);
+        self.metallic.refresh(program);
+        self.roughness.refresh(program);
+        self.emissive.refresh(program);
+        self.alpha_cutoff.refresh(program);
+        self.base_color_texture.refresh(program);
+        self.metallic_roughness_texture.refresh(program);
+    }
+}
+
+// Stand-ins for the textures a material doesn't have. Textures are multiplied with the material
+// factors, so white leaves the factors alone
+struct DefaultTextures {
+    base_color: Texture2D,
+    metallic_roughness: Texture2D,
+}
+
+// A mesh of the loaded model, placed in the world and ready to draw. Meshes that share a texture
+// share its upload
+struct ModelDraw {
+    mesh: mesh::GpuMesh,
+    transform: glm::Mat4,
+    base_color: glm::Vec4,
+    base_color_texture: Option<Rc<Texture2D>>,
+    metallic: f32,
+    roughness: f32,
+    metallic_roughness_texture: Option<Rc<Texture2D>>,
+    emissive: glm::Vec3,
+    // Fragments with less alpha are discarded
+    alpha_cutoff: f32,
+    // Whether back faces are drawn too
+    double_sided: bool,
+    // Whether it is see-through, and so has to be drawn after the opaque draws and the sky
+    blended: bool,
+    // In world space
+    bounds: Option<(glm::Vec3, glm::Vec3)>,
+}
+
+impl ModelDraw {
+    unsafe fn draw(&self, uniforms: &MeshUniforms, defaults: &DefaultTextures) {
+        let base_color_texture = self.base_color_texture.as_deref();
+        base_color_texture
+            .unwrap_or(&defaults.base_color)
+            .bind_to(&uniforms.base_color_texture, TextureUnit(0));
+        let metallic_roughness_texture = self.metallic_roughness_texture.as_deref();
+        metallic_roughness_texture
+            .unwrap_or(&defaults.metallic_roughness)
+            .bind_to(&uniforms.metallic_roughness_texture, TextureUnit(1));
+        uniforms.model.set(&self.transform);
+        uniforms.base_color.set(&self.base_color);
+        uniforms.metallic.set(&self.metallic);
+        uniforms.roughness.set(&self.roughness);
+        uniforms.emissive.set(&self.emissive);
+        uniforms.alpha_cutoff.set(&self.alpha_cutoff);
+        if self.double_sided {
+            gl::Disable(gl::CULL_FACE);
+        }
+        self.mesh.draw();
+        gl::Enable(gl::CULL_FACE);
+    }
+}
+
+// Loads an OBJ or glTF file and uploads its meshes along with their textures. glTF files may also
+// bring cameras, in which case `camera` is moved to the first one
+unsafe fn load_model(path: &str, camera: &mut Camera) -> Vec<ModelDraw> {
+    let is_gltf = path.ends_with(".gltf") || path.ends_with(".glb");
+    if is_gltf {
+        let scene = scene::Scene::load(path).unwrap_or_else(|e| panic!("{}", e));
+        println!(
+            "Loaded {} meshes, {} materials, {} images and {} cameras from {}",
+            scene.meshes.len(),
+            scene.materials.len(),
+            scene.images.len(),
+            scene.cameras.len(),
+            path
+        );
+        for material in &scene.materials {
+            println!("Material: {}", material.name);
+        }
+
+        // glTF materials say which kind of data their textures hold, so an image is assumed to
+        // only be used as one kind
+        let mut textures: HashMap<usize, Rc<Texture2D>> = HashMap::new();
+        let mut texture = |image: Option<usize>, color_space: ColorSpace| {
+            image.map(|image| {
+                let upload = || Rc::new(Texture2D::from_image(&scene.images[image], color_space));
+                Rc::clone(textures.entry(image).or_insert_with(upload))
+            })
+        };
+        let mut draws = vec![];
+        let mut placed_camera = false;
+        for &(node, transform) in &scene.visited {
+            if let Some(mesh) = scene.nodes[node].mesh {
+                for primitive in &scene.meshes[mesh] {
+                    let material = scene.material(primitive);
+                    let mut base_color = material.base_color;
+                    // Opaque materials ignore the alpha they are given
+                    if material.alpha_mode == scene::AlphaMode::Opaque {
+                        base_color.w = 1.0;
+                    }
+                    draws.push(ModelDraw {
+                        mesh: primitive.upload(),
+                        transform,
+                        base_color,
+                        base_color_texture: texture(material.base_color_texture, ColorSpace::Srgb),
+                        metallic: material.metallic,
+                        roughness: material.roughness,
+                        metallic_roughness_texture: texture(
+                            material.metallic_roughness_texture,
+                            ColorSpace::Linear,
+                        ),
+                        emissive: material.emissive,
+                        alpha_cutoff: match material.alpha_mode {
+                            scene::AlphaMode::Mask => material.alpha_cutoff,
+                            _ => 0.0,
+                        },
+                        double_sided: material.double_sided,
+                        blended: material.alpha_mode == scene::AlphaMode::Blend,
+                        bounds: primitive
+                            .bounds()
+                            .map(|bounds| mesh::transform_bounds(bounds, &transform)),
+                    });
+                }
+            }
+            if let (false, Some(index)) = (placed_camera, scene.nodes[node].camera) {
+                let scene_camera = &scene.cameras[index];
+                println!("Viewing through camera {}", scene_camera.name);
+                camera.set_transform(&transform);
+                // The window decides the aspect ratio, whatever the file asks for
+                match scene_camera.projection {
+                    scene::Projection::Perspective { yfov, znear, zfar, .. } => {
+                        camera.set_fov(yfov.to_degrees());
+                        camera.set_clip_planes(znear, zfar.unwrap_or_else(|| camera.far()));
+                    }
+                    scene::Projection::Orthographic { .. } => println!(
+                        "WARNING: camera {} is orthographic, so only its placement is used",
+                        scene_camera.name
+                    ),
+                }
+                placed_camera = true;
+            }
+        }
+        draws
+    } else {
+        let mesh::ObjFile { meshes, materials } =
+            mesh::load_obj(path).unwrap_or_else(|e| panic!("{}", e));
+        let materials = materials.unwrap_or_else(|e| {
+            println!("WARNING: {}", e);
+            vec![]
+        });
+        for mesh in &meshes {
+            println!("Loaded mesh {} from {}", mesh.name, path);
+        }
+        for material in &materials {
+            println!("Material: {}", material.name);
+        }
+        let mut textures: HashMap<usize, Option<Rc<Texture2D>>> = HashMap::new();
+        let draws = meshes
+            .iter()
+            .map(|mesh| {
+                let material = mesh.material.and_then(|i| materials.get(i).map(|m| (i, m)));
+                let opacity = material.map_or(1.0, |(_, m)| m.opacity);
+                let texture = material.and_then(|(i, material)| {
+                    let path = material.diffuse_texture.as_ref()?;
+                    let upload = || match Texture2D::load(&path.to_string_lossy(), ColorSpace::Srgb) {
+                        Ok(texture) => Some(Rc::new(texture)),
+                        Err(e) => {
+                            println!("WARNING: {}", e);
+                            None
+                        }
+                    };
+                    textures.entry(i).or_insert_with(upload).clone()
+                });
+                ModelDraw {
+                    mesh: mesh.upload(),
+                    transform: glm::identity(),
+                    base_color: material.map_or(glm::vec4(0.8, 0.8, 0.8, 1.0), |(_, m)| {
+                        glm::vec4(m.diffuse.x, m.diffuse.y, m.diffuse.z, opacity)
+                    }),
+                    base_color_texture: texture,
+                    metallic: 0.0,
+                    roughness: 1.0,
+                    metallic_roughness_texture: None,
+                    emissive: glm::Vec3::zeros(),
+                    alpha_cutoff: 0.0,
+                    double_sided: false,
+                    blended: opacity < 1.0,
+                    bounds: mesh.bounds(),
+                }
+            })
+            .collect();
+        draws
+    }
+}
+
+// Loads the skybox named on the command line, as described by `Options::skybox`
+unsafe fn load_skybox(path: &str) -> Result<Cubemap, TextureError> {
+    if !std::path::Path::new(path).is_dir() {
+        return Cubemap::load_equirectangular(path, ColorSpace::Srgb, 1024);
+    }
+    let files: Vec<std::path::PathBuf> = std::fs::read_dir(path)
+        .map(|entries| entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect())
+        .unwrap_or_default();
+    let face = |name: &str| {
+        let file = files.iter().find(|file| file.file_stem().is_some_and(|stem| stem == name));
+        match file {
+            Some(file) => file.to_string_lossy().into_owned(),
+            None => panic!("{} has no cubemap face named {}", path, name),
+        }
+    };
+    let faces = ["px", "nx", "py", "ny", "pz", "nz"].map(face);
+    let [px, nx, py, ny, pz, nz] = &faces;
+    Cubemap::load([px, nx, py, ny, pz, nz], ColorSpace::Srgb)
+}
+
+// A model loaded from the command line, along with the program drawing it
+struct Model {
+    draws: Vec<ModelDraw>,
+    program: WatchedShader,
+    uniforms: MeshUniforms,
+    defaults: DefaultTextures,
+}
+
+impl Model {
+    // Files without any meshes give `None`, though their cameras are still used
+    unsafe fn load(path: &str, camera: &mut Camera) -> Option<Model> {
+        let draws = load_model(path, camera);
+        if draws.is_empty() {
+            return None;
+        }
+        let program = shader::ShaderBuilder::new()
+            .include_dir("./shaders/include")
+            .attach_file("./shaders/mesh.frag")
+            .and_then(|b| b.attach_file("./shaders/mesh.vert"))
+            .and_then(|b| b.link_watched())
+            .unwrap_or_else(|e| panic!("{}", e));
+        Some(Model {
+            draws,
+            uniforms: MeshUniforms::new(&program),
+            defaults: DefaultTextures {
+                base_color: Texture2D::solid([255; 4], ColorSpace::Srgb),
+                metallic_roughness: Texture2D::solid([255; 4], ColorSpace::Linear),
+            },
+            program,
+        })
+    }
+
+    // The uniforms and the block binding belong to the old program, so they have to be set up
+    // again
+    unsafe fn reload_if_changed(&mut self, frame_block: &UniformBlock<FrameBlock>) {
+        if self.program.reload_if_changed() {
+            self.uniforms.refresh(&self.program);
+            frame_block.bind_to(&self.program, FRAME_BLOCK);
+        }
+    }
+
+    // Draws either the opaque meshes or the see-through ones
+    unsafe fn draw(&self, blended: bool) {
+        self.program.activate();
+        for draw in self.draws.iter().filter(|draw| draw.blended == blended) {
+            draw.draw(&self.uniforms, &self.defaults);
+        }
+    }
+}
+
+pub struct Renderer {
+    pub camera: Camera,
+    // What the orbit camera frames: the loaded model, or else the triangles
+    pub frame_bounds: (glm::Vec3, glm::Vec3),
+    // Of the framebuffer being drawn to, in pixels
+    size: (u32, u32),
+    // Drawn into instead of the window when rendering offscreen
+    target: Option<RenderTarget>,
+    vao: VertexArray,
+    index_count: i32,
+    shaders: ShaderPermutations,
+    uniforms: Vec<Uniforms>,
+    shading: Shading,
+    frame: FrameBlock,
+    frame_block: UniformBlock<FrameBlock>,
+    model: Option<Model>,
+    sky: Option<(Skybox, Cubemap)>,
+    particles: Particles,
+    show_particles: bool,
+    // When the previous frame was drawn, in seconds, so the particles know how far to move
+    time: f32,
+}
+
+// Sets up the fixed function state every frame relies on and prints what the context runs on
+pub unsafe fn setup_gl() {
+    gl::Enable(gl::DEPTH_TEST);
+    gl::DepthFunc(gl::LESS);
+    gl::Enable(gl::CULL_FACE);
+    gl::Disable(gl::MULTISAMPLE);
+    gl::Enable(gl::BLEND);
+    gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+    gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+    // Shaders work in linear color, and sRGB textures are decoded to it when sampled. Writes to
+    // sRGB framebuffers, such as the window and `RenderTarget`, are encoded back, which also makes
+    // blending happen in linear color
+    gl::Enable(gl::FRAMEBUFFER_SRGB);
+    gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+    gl::DebugMessageCallback(Some(util::debug_callback), ptr::null());
+
+    // Print some diagnostics
+    println!(
+        "{}: {}",
+        util::get_gl_string(gl::VENDOR),
+        util::get_gl_string(gl::RENDERER)
+    );
+    println!("OpenGL\t: {}", util::get_gl_string(gl::VERSION));
+    println!(
+        "GLSL\t: {}",
+        util::get_gl_string(gl::SHADING_LANGUAGE_VERSION)
+    );
+}
+
+impl Renderer {
+    // Sets up the scene described by the command line, for a window of `width` by `height` pixels
+    pub unsafe fn new(options: &Options, width: u32, height: u32) -> Renderer {
+        Renderer::with_target(options, width, height, None)
+    }
+
+    // Like `new`, but draws into a `RenderTarget` instead of the window
+    pub unsafe fn offscreen(options: &Options, width: u32, height: u32) -> Renderer {
+        let target = RenderTarget::new(width, height);
+        Renderer::with_target(options, width, height, Some(target))
+    }
+
+    unsafe fn with_target(
+        options: &Options,
+        width: u32,
+        height: u32,
+        target: Option<RenderTarget>,
+    ) -> Renderer {
+        // == // Set up your VAO here
+        let verticies: Vec<f32> = vec![
+            // Triangle 0
+             0.0,  1.0,  -1.5,
+            -1.0, -1.0,  -1.5,
+             1.0, -1.0,  -1.5,
+             // Triangle 1
+             0.0,  0.5,  -1.25,
+            -0.5, -0.5,  -1.25,
+             0.5, -0.5,  -1.25,
+             // Triangle 2
+             0.0,   0.25,  -1.0,
+            -0.25, -0.25,  -1.0,
+             0.25, -0.25,  -1.0,
+        ];
+        let indicies: Vec<u32> = vec![
+            // Triangle 0
+            0, 1, 2,
+            // Triangle 1
+            3, 4, 5,
+            // Triangle 2
+            6, 7, 8,
+        ];
+        let colors: Vec<f32> = vec![
+            // Triangle 0
+            1.0, 0.0, 0.0, 0.5,
+            1.0, 0.0, 0.0, 0.5,
+            1.0, 0.0, 0.0, 0.5,
+            // Triangle 1
+            0.0, 1.0, 0.0, 0.5,
+            0.0, 1.0, 0.0, 0.5,
+            0.0, 1.0, 0.0, 0.5,
+            // Triangle 2
+            0.0, 0.0, 1.0, 0.5,
+            0.0, 0.0, 1.0, 0.5,
+            0.0, 0.0, 1.0, 0.5,
+        ];
+        // Positions and colors live in separate buffers, one attribute each
+        let position_layout = VertexLayout::single(VertexAttribute::new::<f32>(0, 3));
+        let color_layout = VertexLayout::single(VertexAttribute::new::<f32>(1, 4));
+        let vao_builder = VertexArrayBuilder::new()
+            .buffer(&verticies, &position_layout)
+            .buffer(&colors, &color_layout)
+            .indices(&indicies);
+
+        // Basic usage of shader helper:
+        // The example code below returns a shader object, which has the method `.program_id()`.
+        // The snippet is not enough to do the assignment, and will need to be modified (outside of
+        // just using the correct path), but it only needs to be called once
+        //
+        //     shader::ShaderBuilder::new()
+        //        .attach_file("./path/to/shader.file")?
+        //        .link()?;
+        let mut shaders = ShaderPermutations::new(&[
+            "./shaders/simple.frag",
+            "./shaders/simple.vert",
+        ])
+        .include_dir("./shaders/include");
+        // Build every variant up front, so switching shading never stalls a frame
+        let uniforms: Vec<Uniforms> = Shading::ALL
+            .iter()
+            .map(|shading| shaders.get(shading.defines()).map(Uniforms::new))
+            .collect::<Result<_, _>>()
+            .unwrap_or_else(|e| panic!("{}", e));
+
+        // Make sure the buffers provide every input the vertex shaders read
+        for shading in Shading::ALL.iter() {
+            let program = shaders.get(shading.defines()).unwrap_or_else(|e| panic!("{}", e));
+            for problem in vao_builder.check(&program.reflect()) {
+                println!("WARNING: {}", problem);
+            }
+        }
+        let vao = vao_builder.build();
+
+        let mut camera = Camera::new(1.0);
+        camera.set_fov(75.0);
+        camera.set_clip_planes(0.1, 100.0);
+
+        // Optionally load a model given on the command line, e.g. `cargo run -- model.gltf`
+        let model = options.model.as_ref().and_then(|path| Model::load(path, &mut camera));
+
+        // Without a skybox the scene is drawn on flat black
+        let sky = options.skybox.as_ref().map(|path| {
+            let cubemap = load_skybox(path).unwrap_or_else(|e| panic!("{}", e));
+            (Skybox::new().unwrap_or_else(|e| panic!("{}", e)), cubemap)
+        });
+
+        let frame = FrameBlock {
+            view: camera.view(),
+            projection: camera.projection(),
+            light_direction: glm::normalize(&glm::vec3(0.8, 1.0, 0.6)),
+            ambient: 0.2,
+            light_color: glm::vec3(0.8, 0.8, 0.8),
+            time: 0.0,
+            screen_dims: glm::vec2(width as f32, height as f32),
+        };
+
+        // Every variant reads the camera, the light and the time from this block, which is filled
+        // in once per frame
+        let frame_block = UniformBlock::new(FRAME_BINDING, &frame);
+        for shading in Shading::ALL.iter() {
+            let program = shaders.get(shading.defines()).unwrap_or_else(|e| panic!("{}", e));
+            frame_block.bind_to(program, FRAME_BLOCK);
+        }
+        if let Some(model) = &model {
+            frame_block.bind_to(&model.program, FRAME_BLOCK);
+        }
+
+        // A GPU particle fountain in front of the triangles, toggled with P
+        let particles = Particles::new(&frame_block, glm::vec3(0.0, -1.0, -2.0))
+            .unwrap_or_else(|e| panic!("{}", e));
+
+        let model_bounds = model
+            .iter()
+            .flat_map(|model| &model.draws)
+            .filter_map(|draw| draw.bounds)
+            .reduce(mesh::union_bounds);
+        let triangle_bounds = verticies
+            .chunks_exact(3)
+            .map(|p| glm::vec3(p[0], p[1], p[2]))
+            .fold(None, |bounds, p| match bounds {
+                Some(bounds) => Some(mesh::union_bounds(bounds, (p, p))),
+                None => Some((p, p)),
+            });
+
+        let mut renderer = Renderer {
+            camera,
+            frame_bounds: model_bounds.or(triangle_bounds).unwrap(),
+            size: (width, height),
+            target,
+            vao,
+            index_count: indicies.len() as i32,
+            shaders,
+            uniforms,
+            shading: Shading::Unlit,
+            frame,
+            frame_block,
+            model,
+            sky,
+            particles,
+            show_particles: false,
+            time: 0.0,
+        };
+        renderer.resize(width, height);
+        renderer
+    }
+
+    // Follows a change in the size of the window, or of the image rendered offscreen
+    pub unsafe fn resize(&mut self, width: u32, height: u32) {
+        self.size = (width, height);
+        match &mut self.target {
+            Some(target) => {
+                target.resize(width, height);
+                target.bind();
+            }
+            None => gl::Viewport(0, 0, width as i32, height as i32),
+        }
+        self.camera.set_aspect(width, height);
+        self.frame.screen_dims = glm::vec2(width as f32, height as f32);
+    }
+
+    // The shading after the current one, wrapping around
+    pub fn next_shading(&mut self) {
+        self.shading = self.shading.next();
+        println!("Shading: {:?}", self.shading);
+    }
+
+    // Starts the fountain over from the origin when it is shown again
+    pub unsafe fn toggle_particles(&mut self) {
+        self.show_particles = !self.show_particles;
+        if self.show_particles {
+            self.particles.restart();
+        }
+    }
+
+    // Prints the inputs and uniforms of the current shading
+    pub unsafe fn print_interface(&mut self) {
+        let program = self
+            .shaders
+            .get(self.shading.defines())
+            .unwrap_or_else(|e| panic!("{}", e));
+        println!("{:?} shading interface:\n{}", self.shading, program.reflect());
+    }
+
+    // The last frame drawn, top row first. Without a target this reads the back buffer of the
+    // window, so it has to be called before the buffers are swapped
+    pub unsafe fn read_pixels(&self) -> image::RgbaImage {
+        let (width, height) = self.size;
+        match &self.target {
+            Some(target) => target.read_pixels(),
+            None => render_target::read_pixels(0, width, height),
+        }
+    }
+
+    // Draws a frame at `time` seconds into the animation
+    pub unsafe fn draw(&mut self, time: f32) {
+        let delta_time = (time - self.time).max(0.0);
+        self.time = time;
+
+        // Pick up edits to the shader files without restarting. The uniforms and the block
+        // binding belong to the old programs, so they have to be set up again. Every value is
+        // uploaded below anyway
+        if self.shaders.reload_if_changed() {
+            for (shading, variant) in Shading::ALL.iter().zip(self.uniforms.iter_mut()) {
+                let program = self
+                    .shaders
+                    .get(shading.defines())
+                    .unwrap_or_else(|e| panic!("{}", e));
+                variant.refresh(program);
+                self.frame_block.bind_to(program, FRAME_BLOCK);
+            }
+        }
+        self.particles.reload_if_changed(&self.frame_block);
+        if let Some(model) = &mut self.model {
+            model.reload_if_changed(&self.frame_block);
+        }
+
+        self.frame.view = self.camera.view();
+        self.frame.projection = self.camera.projection();
+        self.frame.time = time;
+        self.frame_block.update(&self.frame);
+        let active = Shading::ALL.iter().position(|&s| s == self.shading).unwrap();
+        self.uniforms[active].model.set(&glm::identity::<f32, 4>());
+
+        gl::ClearColor(0.0, 0.0, 0.0, 1.0); // moon raker, full opacity
+        gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+
+        // Issue the necessary commands to draw your scene here
+        // Opaque geometry goes first, so the sky only fills in the pixels it left
+        if let Some(model) = &self.model {
+            model.draw(false);
+        }
+
+        if let Some((skybox, cubemap)) = &self.sky {
+            skybox.draw(cubemap, &self.camera.view_projection());
+        }
+
+        // Everything see-through blends with what is already there, the sky included
+        self.vao.bind();
+        self.shaders
+            .get(self.shading.defines())
+            .unwrap_or_else(|e| panic!("{}", e))
+            .activate();
+        gl::DrawElements(
+            gl::TRIANGLES,
+            self.index_count,
+            gl::UNSIGNED_INT,
+            ptr::null(),
+        );
+
+        if let Some(model) = &self.model {
+            model.draw(true);
+        }
+
+        if self.show_particles {
+            self.particles.step_and_draw(delta_time);
+        }
+    }
+}
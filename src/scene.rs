@@ -0,0 +1,604 @@
+// Scenes imported from glTF 2.0 files (.gltf and .glb).
+//
+// Importing only touches the CPU: meshes, materials, images, nodes and cameras end up in plain
+// structs, and nothing is sent to OpenGL until a mesh is uploaded with `Mesh::upload`. This means
+// a scene can be loaded and inspected without a GL context.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use image::{DynamicImage, ImageBuffer};
+
+use crate::mesh::{Mesh, MeshVertex};
+
+#[derive(Clone, Debug)]
+pub struct Scene {
+    // A glTF mesh is made of primitives which may each use a different material, so every
+    // primitive becomes its own `Mesh`
+    pub meshes: Vec<Vec<Mesh>>,
+    pub materials: Vec<PbrMaterial>,
+    pub images: Vec<DynamicImage>,
+    pub cameras: Vec<Camera>,
+    // Every node in the file. The hierarchy is stored through `parent` and `children` indices
+    pub nodes: Vec<Node>,
+    // The top level nodes of the scene that was picked for display
+    pub roots: Vec<usize>,
+    // Every node reachable from the roots along with its world transform, parents before children
+    pub visited: Vec<(usize, glm::Mat4)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    // Relative to the parent node
+    pub transform: glm::Mat4,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub mesh: Option<usize>,
+    pub camera: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlphaMode {
+    Opaque,
+    // Fragments with an alpha below `PbrMaterial::alpha_cutoff` are discarded
+    Mask,
+    Blend,
+}
+
+// The metallic-roughness material model of glTF. Textures are indices into `Scene::images`, and
+// are sampled with the first set of texture coordinates
+#[derive(Clone, Debug)]
+pub struct PbrMaterial {
+    pub name: String,
+    pub base_color: glm::Vec4,
+    // Holds sRGB colors, which are multiplied with `base_color`
+    pub base_color_texture: Option<usize>,
+    pub metallic: f32,
+    pub roughness: f32,
+    // Roughness in the green channel and metalness in the blue channel, multiplied with
+    // `roughness` and `metallic`
+    pub metallic_roughness_texture: Option<usize>,
+    pub emissive: glm::Vec3,
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    Perspective {
+        // Vertical field of view in radians
+        yfov: f32,
+        // When missing, the aspect ratio of the viewport should be used
+        aspect_ratio: Option<f32>,
+        znear: f32,
+        // When missing, the projection is infinite
+        zfar: Option<f32>,
+    },
+    Orthographic {
+        xmag: f32,
+        ymag: f32,
+        znear: f32,
+        zfar: f32,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub name: String,
+    pub projection: Projection,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Gltf {
+        path: Option<PathBuf>,
+        source: gltf::Error,
+    },
+    // The node is its own ancestor, or the child of more than one node. Either way the nodes don't
+    // form the tree glTF requires, and walking them would never end or visit nodes twice
+    InvalidHierarchy { node: usize },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Gltf {
+                path: Some(path),
+                source,
+            } => write!(f, "failed to import {}: {}", path.display(), source),
+            SceneError::Gltf { path: None, source } => {
+                write!(f, "failed to import glTF data: {}", source)
+            }
+            SceneError::InvalidHierarchy { node } => write!(
+                f,
+                "node {} appears more than once in the node hierarchy, which has to be a tree",
+                node
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SceneError::Gltf { source, .. } => Some(source),
+            SceneError::InvalidHierarchy { .. } => None,
+        }
+    }
+}
+
+impl Default for PbrMaterial {
+    // The material glTF uses for primitives that don't name one
+    fn default() -> PbrMaterial {
+        PbrMaterial {
+            name: String::new(),
+            base_color: glm::vec4(1.0, 1.0, 1.0, 1.0),
+            base_color_texture: None,
+            metallic: 1.0,
+            roughness: 1.0,
+            metallic_roughness_texture: None,
+            emissive: glm::Vec3::zeros(),
+            alpha_mode: AlphaMode::Opaque,
+            alpha_cutoff: 0.5,
+            double_sided: false,
+        }
+    }
+}
+
+impl Scene {
+    // Imports a .gltf or .glb file. Buffers and images may be embedded or stored next to the file
+    pub fn load(path: &str) -> Result<Scene, SceneError> {
+        let path = Path::new(path);
+        let (document, buffers, images) = gltf::import(path).map_err(|source| SceneError::Gltf {
+            path: Some(path.to_path_buf()),
+            source,
+        })?;
+        Scene::from_gltf(&document, &buffers, images)
+    }
+
+    fn from_gltf(
+        document: &gltf::Document,
+        buffers: &[gltf::buffer::Data],
+        images: Vec<gltf::image::Data>,
+    ) -> Result<Scene, SceneError> {
+        let meshes = document
+            .meshes()
+            .map(|mesh| {
+                mesh.primitives()
+                    .filter_map(|primitive| convert_primitive(&mesh, &primitive, buffers))
+                    .collect()
+            })
+            .collect();
+
+        let mut nodes: Vec<Node> = document
+            .nodes()
+            .map(|node| Node {
+                transform: node.transform().matrix().into(),
+                parent: None,
+                children: node.children().map(|child| child.index()).collect(),
+                mesh: node.mesh().map(|mesh| mesh.index()),
+                camera: node.camera().map(|camera| camera.index()),
+            })
+            .collect();
+        for parent in 0..nodes.len() {
+            for child in nodes[parent].children.clone() {
+                if nodes[child].parent.is_some() {
+                    return Err(SceneError::InvalidHierarchy { node: child });
+                }
+                nodes[child].parent = Some(parent);
+            }
+        }
+
+        // Files without a scene are allowed, in which case every node without a parent is shown
+        let roots = match document.default_scene().or_else(|| document.scenes().next()) {
+            Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+            None => (0..nodes.len())
+                .filter(|&i| nodes[i].parent.is_none())
+                .collect(),
+        };
+
+        let mut scene = Scene {
+            meshes,
+            materials: document.materials().map(convert_material).collect(),
+            images: images.into_iter().map(convert_image).collect(),
+            cameras: document.cameras().map(convert_camera).collect(),
+            nodes,
+            roots,
+            visited: vec![],
+        };
+        // With one parent per node, any cycle shows up as a parent chain that never ends. Roots
+        // that are also children of another node are caught by `visit`
+        for node in 0..scene.nodes.len() {
+            scene.world_transform(node)?;
+        }
+        scene.visited = scene.visit()?;
+        Ok(scene)
+    }
+
+    // The transform from the node's local space to world space
+    pub fn world_transform(&self, node: usize) -> Result<glm::Mat4, SceneError> {
+        let mut seen = vec![false; self.nodes.len()];
+        seen[node] = true;
+        let mut transform = self.nodes[node].transform;
+        let mut current = self.nodes[node].parent;
+        while let Some(parent) = current {
+            if std::mem::replace(&mut seen[parent], true) {
+                return Err(SceneError::InvalidHierarchy { node: parent });
+            }
+            transform = self.nodes[parent].transform * transform;
+            current = self.nodes[parent].parent;
+        }
+        Ok(transform)
+    }
+
+    // Walks the hierarchy from the roots to find `visited`
+    fn visit(&self) -> Result<Vec<(usize, glm::Mat4)>, SceneError> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut visited = Vec::with_capacity(self.nodes.len());
+        let mut stack: Vec<(usize, glm::Mat4)> = self
+            .roots
+            .iter()
+            .rev()
+            .map(|&root| (root, glm::identity()))
+            .collect();
+        while let Some((node, parent_transform)) = stack.pop() {
+            if std::mem::replace(&mut seen[node], true) {
+                return Err(SceneError::InvalidHierarchy { node });
+            }
+            let transform = parent_transform * self.nodes[node].transform;
+            visited.push((node, transform));
+            for &child in self.nodes[node].children.iter().rev() {
+                stack.push((child, transform));
+            }
+        }
+        Ok(visited)
+    }
+
+    // The material a primitive should be drawn with
+    pub fn material(&self, mesh: &Mesh) -> PbrMaterial {
+        mesh.material
+            .and_then(|i| self.materials.get(i))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+fn convert_primitive(
+    mesh: &gltf::Mesh,
+    primitive: &gltf::Primitive,
+    buffers: &[gltf::buffer::Data],
+) -> Option<Mesh> {
+    let name = mesh.name().unwrap_or_default();
+    if primitive.mode() != gltf::mesh::Mode::Triangles {
+        println!(
+            "WARNING: skipping primitive {} of mesh '{}', only triangles are supported (found {:?})",
+            primitive.index(),
+            name,
+            primitive.mode()
+        );
+        return None;
+    }
+
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+    let positions = match reader.read_positions() {
+        Some(positions) => positions,
+        None => {
+            println!(
+                "WARNING: skipping primitive {} of mesh '{}', it has no positions",
+                primitive.index(),
+                name
+            );
+            return None;
+        }
+    };
+    let mut vertices: Vec<MeshVertex> = positions
+        .map(|position| MeshVertex {
+            position: position.into(),
+            ..Default::default()
+        })
+        .collect();
+
+    let has_normals = match reader.read_normals() {
+        Some(normals) => {
+            for (vertex, normal) in vertices.iter_mut().zip(normals) {
+                vertex.normal = normal.into();
+            }
+            true
+        }
+        None => false,
+    };
+    if let Some(uvs) = reader.read_tex_coords(0) {
+        // glTF puts v = 0 at the top of the image, while textures are uploaded bottom row first
+        for (vertex, uv) in vertices.iter_mut().zip(uvs.into_f32()) {
+            vertex.uv = glm::vec2(uv[0], 1.0 - uv[1]);
+        }
+    }
+    // Primitives without indices draw their vertices in order
+    let indices = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertices.len() as u32).collect(),
+    };
+
+    let mut mesh = Mesh {
+        name: name.to_string(),
+        vertices,
+        indices,
+        material: primitive.material().index(),
+    };
+    if !has_normals {
+        mesh.compute_normals();
+    }
+    Some(mesh)
+}
+
+// The image a material texture samples. Only the first set of texture coordinates is imported,
+// so textures that use another set are left out
+fn texture_image(material: &gltf::Material, info: gltf::texture::Info) -> Option<usize> {
+    if info.tex_coord() != 0 {
+        println!(
+            "WARNING: ignoring a texture of material '{}', it uses texture coordinates {}",
+            material.name().unwrap_or_default(),
+            info.tex_coord()
+        );
+        return None;
+    }
+    Some(info.texture().source().index())
+}
+
+fn convert_material(material: gltf::Material) -> PbrMaterial {
+    let pbr = material.pbr_metallic_roughness();
+    PbrMaterial {
+        name: material.name().unwrap_or_default().to_string(),
+        base_color: pbr.base_color_factor().into(),
+        base_color_texture: pbr
+            .base_color_texture()
+            .and_then(|info| texture_image(&material, info)),
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        metallic_roughness_texture: pbr
+            .metallic_roughness_texture()
+            .and_then(|info| texture_image(&material, info)),
+        emissive: material.emissive_factor().into(),
+        alpha_mode: match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask,
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        },
+        alpha_cutoff: material.alpha_cutoff().unwrap_or(0.5),
+        double_sided: material.double_sided(),
+    }
+}
+
+fn convert_camera(camera: gltf::Camera) -> Camera {
+    let projection = match camera.projection() {
+        gltf::camera::Projection::Perspective(p) => Projection::Perspective {
+            yfov: p.yfov(),
+            aspect_ratio: p.aspect_ratio(),
+            znear: p.znear(),
+            zfar: p.zfar(),
+        },
+        gltf::camera::Projection::Orthographic(o) => Projection::Orthographic {
+            xmag: o.xmag(),
+            ymag: o.ymag(),
+            znear: o.znear(),
+            zfar: o.zfar(),
+        },
+    };
+    Camera {
+        name: camera.name().unwrap_or_default().to_string(),
+        projection,
+    }
+}
+
+// 16 bit images arrive as native endian bytes
+fn to_u16(bytes: Vec<u8>) -> Vec<u16> {
+    bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_ne_bytes([pair[0], pair[1]]))
+        .collect()
+}
+
+fn convert_image(data: gltf::image::Data) -> DynamicImage {
+    use gltf::image::Format;
+
+    let (width, height) = (data.width, data.height);
+    let image = match data.format {
+        Format::R8 => ImageBuffer::from_raw(width, height, data.pixels).map(DynamicImage::ImageLuma8),
+        Format::R8G8 => {
+            ImageBuffer::from_raw(width, height, data.pixels).map(DynamicImage::ImageLumaA8)
+        }
+        Format::R8G8B8 => {
+            ImageBuffer::from_raw(width, height, data.pixels).map(DynamicImage::ImageRgb8)
+        }
+        Format::R8G8B8A8 => {
+            ImageBuffer::from_raw(width, height, data.pixels).map(DynamicImage::ImageRgba8)
+        }
+        Format::B8G8R8 => {
+            ImageBuffer::from_raw(width, height, data.pixels).map(DynamicImage::ImageBgr8)
+        }
+        Format::B8G8R8A8 => {
+            ImageBuffer::from_raw(width, height, data.pixels).map(DynamicImage::ImageBgra8)
+        }
+        Format::R16 => {
+            ImageBuffer::from_raw(width, height, to_u16(data.pixels)).map(DynamicImage::ImageLuma16)
+        }
+        Format::R16G16 => ImageBuffer::from_raw(width, height, to_u16(data.pixels))
+            .map(DynamicImage::ImageLumaA16),
+        Format::R16G16B16 => ImageBuffer::from_raw(width, height, to_u16(data.pixels))
+            .map(DynamicImage::ImageRgb16),
+        Format::R16G16B16A16 => ImageBuffer::from_raw(width, height, to_u16(data.pixels))
+            .map(DynamicImage::ImageRgba16),
+    };
+    // The importer decoded these pixels itself, so the sizes always match
+    image.expect("glTF image data doesn't match its dimensions")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HIERARCHY: &str = "tests/scenes/hierarchy.gltf";
+
+    // Imports a variation of the hierarchy scene. Its buffers and images are embedded, so the
+    // file can be written anywhere
+    fn load_edited(name: &str, edit: impl Fn(&str) -> String) -> Result<Scene, SceneError> {
+        let gltf = edit(&std::fs::read_to_string(HIERARCHY).unwrap());
+        let path = std::env::temp_dir().join(format!("gloom-rs-{}-{}.gltf", name, std::process::id()));
+        std::fs::write(&path, gltf).unwrap();
+        let scene = Scene::load(path.to_str().unwrap());
+        let _ = std::fs::remove_file(&path);
+        scene
+    }
+
+    fn origin(transform: &glm::Mat4) -> glm::Vec3 {
+        (transform * glm::vec4(0.0, 0.0, 0.0, 1.0)).xyz()
+    }
+
+    fn assert_close(actual: glm::Vec3, expected: glm::Vec3) {
+        assert!(
+            glm::distance(&actual, &expected) < 1e-5,
+            "expected {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn meshes() {
+        let scene = Scene::load(HIERARCHY).unwrap();
+        assert_eq!(scene.meshes.len(), 2);
+        let two_materials = &scene.meshes[0];
+        assert_eq!(two_materials.len(), 2);
+        assert_eq!(two_materials[0].name, "two materials");
+        assert_eq!(two_materials[0].material, Some(0));
+        assert_eq!(two_materials[1].material, Some(1));
+        assert_eq!(two_materials[0].vertices.len(), 3);
+        assert_eq!(two_materials[0].indices, [0, 1, 2]);
+
+        // Without indices the vertices are drawn in order, and without normals they're computed
+        let unindexed = &scene.meshes[1][0];
+        assert_eq!(unindexed.indices, [0, 1, 2]);
+        assert_close(unindexed.vertices[0].normal, glm::vec3(0.0, 0.0, 1.0));
+        assert_eq!(scene.material(unindexed).base_color, glm::vec4(1.0, 1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn materials_and_textures() {
+        let scene = Scene::load(HIERARCHY).unwrap();
+        assert_eq!(scene.materials.len(), 2);
+
+        let textured = &scene.materials[0];
+        assert_eq!(textured.name, "textured");
+        assert_eq!(textured.base_color, glm::vec4(0.5, 0.25, 1.0, 0.75));
+        assert_eq!(textured.metallic, 0.1);
+        assert_eq!(textured.roughness, 0.9);
+        assert_eq!(textured.alpha_mode, AlphaMode::Mask);
+        assert_eq!(textured.alpha_cutoff, 0.3);
+        assert!(!textured.double_sided);
+        // Texture 0 samples image 1, so the material has to point at the image
+        assert_eq!(textured.base_color_texture, Some(1));
+        assert_eq!(textured.metallic_roughness_texture, None);
+
+        let glowing = &scene.materials[1];
+        assert_eq!(glowing.emissive, glm::vec3(1.0, 0.5, 0.0));
+        assert_eq!(glowing.base_color_texture, None);
+        assert_eq!(glowing.metallic_roughness_texture, Some(0));
+        assert!(glowing.double_sided);
+
+        assert_eq!(scene.images.len(), 2);
+        assert_eq!(scene.images[0].to_rgba8().get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(scene.images[1].to_rgba8().get_pixel(0, 0).0, [0, 0, 255, 128]);
+    }
+
+    #[test]
+    fn node_transforms() {
+        let scene = Scene::load(HIERARCHY).unwrap();
+        assert_eq!(scene.roots, [0, 3]);
+        assert_eq!(scene.nodes[0].children, [1]);
+        assert_eq!(scene.nodes[1].parent, Some(0));
+        assert_eq!(scene.nodes[2].parent, Some(1));
+        assert_eq!(scene.nodes[3].parent, None);
+
+        // The arm turns a quarter around Y and doubles in size, so the eye one unit along its Z
+        // ends up two units along X from the root
+        let eye = scene.world_transform(2).unwrap();
+        assert_close(origin(&eye), glm::vec3(3.0, 2.0, 3.0));
+        assert_close((eye * glm::vec4(0.0, 1.0, 0.0, 0.0)).xyz(), glm::vec3(0.0, 2.0, 0.0));
+        assert_close(origin(&scene.world_transform(3).unwrap()), glm::vec3(-4.0, 0.0, 0.0));
+
+        let order: Vec<usize> = scene.visited.iter().map(|&(node, _)| node).collect();
+        assert_eq!(order, [0, 1, 2, 3]);
+        for &(node, transform) in &scene.visited {
+            assert_eq!(transform, scene.world_transform(node).unwrap());
+        }
+    }
+
+    #[test]
+    fn cameras() {
+        let scene = Scene::load(HIERARCHY).unwrap();
+        assert_eq!(scene.cameras.len(), 2);
+        assert_eq!(scene.nodes[2].camera, Some(0));
+        assert_eq!(scene.nodes[3].camera, Some(1));
+
+        let eye = &scene.cameras[0];
+        assert_eq!(eye.name, "eye");
+        assert_eq!(
+            eye.projection,
+            Projection::Perspective {
+                yfov: 0.8,
+                aspect_ratio: Some(1.5),
+                znear: 0.1,
+                zfar: Some(50.0)
+            }
+        );
+
+        let top = &scene.cameras[1];
+        assert_eq!(
+            top.projection,
+            Projection::Orthographic {
+                xmag: 2.0,
+                ymag: 1.0,
+                znear: 0.5,
+                zfar: 20.0
+            }
+        );
+    }
+
+    #[test]
+    fn node_cycle() {
+        // The eye becomes the parent of the root, closing the loop root -> arm -> eye -> root
+        let cyclic = load_edited("cycle", |gltf| {
+            gltf.replace("\"name\": \"eye\",", "\"name\": \"eye\", \"children\": [0],")
+        });
+        match cyclic {
+            Err(SceneError::InvalidHierarchy { node }) => assert_eq!(node, 0),
+            other => panic!("expected InvalidHierarchy, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn node_with_two_parents() {
+        let shared = load_edited("two-parents", |gltf| {
+            gltf.replace("\"name\": \"prop\",", "\"name\": \"prop\", \"children\": [1],")
+        });
+        match shared {
+            Err(SceneError::InvalidHierarchy { node }) => assert_eq!(node, 1),
+            other => panic!("expected InvalidHierarchy, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn unreachable_cycle() {
+        // Nodes that no root leads to aren't visited, but their transforms still can't be found
+        let mut scene = Scene::load(HIERARCHY).unwrap();
+        scene.nodes[3].parent = Some(3);
+        assert!(scene.visit().is_ok());
+        assert!(matches!(
+            scene.world_transform(3),
+            Err(SceneError::InvalidHierarchy { node: 3 })
+        ));
+    }
+}
@@ -0,0 +1,28 @@
+// Saving the frame shown in the window as a PNG, for the images in the report.
+//
+// Reading the pixels back has to happen on the render thread, which owns the context, but encoding
+// the PNG takes long enough to make a frame hitch, so that is done on a thread of its own.
+
+use std::path::Path;
+use std::thread;
+
+use crate::util;
+
+// Where screenshots end up, next to the report that uses them
+pub const SCREENSHOT_FOLDER: &str = "./report/images";
+
+// Writes the image to `folder` as screenshot_<timestamp>.png without blocking the caller
+pub fn save_in_background(image: image::RgbaImage, folder: &str) {
+    let destination = Path::new(folder).join(format!("screenshot_{}.png", util::timestamp()));
+    thread::spawn(move || {
+        let saved = destination
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .map_err(image::ImageError::IoError)
+            .and_then(|_| image.save(&destination));
+        match saved {
+            Ok(()) => println!("Saved screenshot {}", destination.display()),
+            Err(e) => println!("WARNING: could not save screenshot {}: {}", destination.display(), e),
+        }
+    });
+}
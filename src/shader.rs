@@ -1,61 +1,204 @@
-use gl;
-use std::{ffi::CString, path::Path, ptr, str};
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, HashMap},
+    error, fmt, io,
+    ffi::CString,
+    path::{Path, PathBuf},
+    ptr, str,
+};
+
+mod binary_cache;
+pub mod block;
+pub mod compute;
+pub mod diagnostics;
+mod preprocess;
+pub mod reflect;
+pub mod uniform;
+mod watch;
+
+use crate::gl_object::Program;
+use diagnostics::{Diagnostic, SourceMap};
+use reflect::UniformInfo;
+use uniform::UniformValue;
+
+pub use watch::WatchedShader;
 
 pub struct Shader {
-    pub program_id: u32,
+    program: Program,
 }
 
 pub struct ShaderBuilder {
-    program_id: u32,
-    shaders: Vec<u32>,
+    program: Program,
+    shaders: CompiledShaders,
+    sources: Vec<ShaderSource>,
+    include_dirs: Vec<PathBuf>,
+    defines: Vec<(String, String)>,
+    // Where linked program binaries are cached, if anywhere. Defaults to gloom-rs/programs in the
+    // per-user cache directory, e.g. ~/.cache on Linux
+    cache_dir: Option<PathBuf>,
+    // Every file read while compiling, so they can be watched for changes
+    dependencies: Vec<PathBuf>,
+}
+
+// Shader objects are only needed until the program is linked, and are deleted along with the builder
+struct CompiledShaders(Vec<u32>);
+
+// A source that has been attached to a builder, but not compiled yet
+#[derive(Clone)]
+struct ShaderSource {
+    stage: ShaderType,
+    file: Option<PathBuf>,
+    text: String,
+}
+
+// Builds and caches variants of the same set of shader files, one for each set of defines. Each
+// variant is rebuilt when its files change, like a `WatchedShader`
+pub struct ShaderPermutations {
+    files: Vec<String>,
+    include_dirs: Vec<String>,
+    variants: HashMap<BTreeMap<String, String>, WatchedShader>,
 }
 
 pub struct ShaderUniform {
+    name: String,
     location: i32,
     program_id: u32,
+    // The type declared in the shader, or None if the uniform isn't active in the program
+    gl_type: Option<gl::types::GLenum>,
+    // Number of elements for arrays, 1 otherwise
+    array_size: usize,
+    // Set once a type mismatch has been reported, so it isn't repeated every frame
+    mismatch_reported: Cell<bool>,
 }
 
 #[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ShaderType {
     Vertex,
     Fragment,
     TessellationControl,
     TessellationEvaluation,
     Geometry,
+    Compute,
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    /// The shader source could not be read from disk
+    Io { path: PathBuf, source: io::Error },
+    /// The file extension does not map to a known shader stage
+    UnknownExtension(PathBuf),
+    /// An `#include` could not be found next to the including file or in any include directory
+    IncludeNotFound {
+        file: Option<PathBuf>,
+        line: u32,
+        include: String,
+    },
+    /// A file ended up including itself. The chain starts and ends with the same file
+    IncludeCycle(Vec<PathBuf>),
+    /// A shader stage failed to compile. `file` is `None` for sources passed in directly.
+    /// `diagnostics` holds the messages of `log` that could be mapped back to a source location
+    Compile {
+        stage: ShaderType,
+        file: Option<PathBuf>,
+        log: String,
+        diagnostics: Vec<Diagnostic>,
+    },
+    /// The program failed to link
+    Link { log: String },
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::Io { path, source } => {
+                write!(f, "failed to read shader source {}: {}", path.display(), source)
+            }
+            ShaderError::UnknownExtension(path) => {
+                write!(f, "unknown shader extension for file {}", path.display())
+            }
+            ShaderError::IncludeNotFound { file, line, include } => {
+                let file = file
+                    .as_ref()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_else(|| "<source>".to_string());
+                write!(f, "{}:{}: could not find included file \"{}\"", file, line, include)
+            }
+            ShaderError::IncludeCycle(chain) => {
+                write!(f, "include cycle detected: ")?;
+                let chain: Vec<String> = chain.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "{}", chain.join(" -> "))
+            }
+            ShaderError::Compile { stage, file, log, diagnostics } => {
+                match file {
+                    Some(file) => write!(f, "{:?} shader {} failed to compile", stage, file.display())?,
+                    None => write!(f, "{:?} shader failed to compile", stage)?,
+                }
+                if diagnostics.is_empty() {
+                    return write!(f, "\n{}", log);
+                }
+                for diagnostic in diagnostics {
+                    write!(f, "\n\n{}", diagnostic)?;
+                }
+                Ok(())
+            }
+            ShaderError::Link { log } => write!(f, "shader program failed to link\n{}", log),
+        }
+    }
+}
+
+impl error::Error for ShaderError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ShaderError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
 }
 
 impl Shader {
+    pub fn program_id(&self) -> u32 {
+        self.program.id()
+    }
+
     // Make sure the shader is active before calling this
     pub unsafe fn get_uniform_location(&self, name: &str) -> i32 {
         let name_cstr = CString::new(name).expect("CString::new failed");
-        gl::GetUniformLocation(self.program_id, name_cstr.as_ptr())
+        gl::GetUniformLocation(self.program_id(), name_cstr.as_ptr())
     }
 
     pub unsafe fn activate(&self) {
-        gl::UseProgram(self.program_id);
+        gl::UseProgram(self.program_id());
+    }
+
+    // Lists the active uniforms, vertex attributes and blocks of the program
+    pub unsafe fn reflect(&self) -> reflect::ProgramReflection {
+        reflect::ProgramReflection::new(self)
     }
 }
 
-impl Into<gl::types::GLenum> for ShaderType {
-    fn into(self) -> gl::types::GLenum {
-        match self {
+impl From<ShaderType> for gl::types::GLenum {
+    fn from(stage: ShaderType) -> gl::types::GLenum {
+        match stage {
             ShaderType::Vertex => gl::VERTEX_SHADER,
             ShaderType::Fragment => gl::FRAGMENT_SHADER,
             ShaderType::TessellationControl => gl::TESS_CONTROL_SHADER,
             ShaderType::TessellationEvaluation => gl::TESS_EVALUATION_SHADER,
             ShaderType::Geometry => gl::GEOMETRY_SHADER,
+            ShaderType::Compute => gl::COMPUTE_SHADER,
         }
     }
 }
 
 impl ShaderType {
     fn from_ext(ext: &std::ffi::OsStr) -> Result<ShaderType, String> {
-        match ext.to_str().expect("Failed to read extension") {
+        match ext.to_str().unwrap_or_default() {
             "vert" => Ok(ShaderType::Vertex),
             "frag" => Ok(ShaderType::Fragment),
             "tcs" => Ok(ShaderType::TessellationControl),
             "tes" => Ok(ShaderType::TessellationEvaluation),
             "geom" => Ok(ShaderType::Geometry),
+            "comp" => Ok(ShaderType::Compute),
             e => Err(e.to_string()),
         }
     }
@@ -64,170 +207,367 @@ impl ShaderType {
 impl ShaderBuilder {
     pub unsafe fn new() -> ShaderBuilder {
         ShaderBuilder {
-            program_id: gl::CreateProgram(),
-            shaders: vec![],
+            program: Program::new(),
+            shaders: CompiledShaders(vec![]),
+            sources: vec![],
+            include_dirs: vec![],
+            defines: vec![],
+            cache_dir: binary_cache::default_dir(),
+            dependencies: vec![],
         }
     }
 
-    pub unsafe fn attach_file(self, shader_path: &str) -> ShaderBuilder {
-        let path = Path::new(shader_path);
-        if let Some(extension) = path.extension() {
-            let shader_type =
-                ShaderType::from_ext(extension).expect("Failed to parse file extension.");
-            let shader_src = std::fs::read_to_string(path)
-                .expect(&format!("Failed to read shader source. {}", shader_path));
-            self.compile_shader(&shader_src, shader_type)
-        } else {
-            panic!(
-                "Failed to read extension of file with path: {}",
-                shader_path
-            );
+    // Adds a directory to search for `#include`d files that aren't next to the including file.
+    // Directories are searched in the order they were added
+    pub fn include_dir(mut self, dir: &str) -> ShaderBuilder {
+        self.include_dirs.push(PathBuf::from(dir));
+        self
+    }
+
+    // Adds `#define name value` right after the `#version` line of every attached source.
+    // Defining the same name twice replaces the earlier value
+    pub fn define(mut self, name: &str, value: &str) -> ShaderBuilder {
+        match self.defines.iter_mut().find(|(n, _)| n == name) {
+            Some(define) => define.1 = value.to_string(),
+            None => self.defines.push((name.to_string(), value.to_string())),
         }
+        self
     }
 
-    pub unsafe fn compile_shader(
+    pub fn attach_file(mut self, shader_path: &str) -> Result<ShaderBuilder, ShaderError> {
+        let path = Path::new(shader_path);
+        let shader_type = path
+            .extension()
+            .and_then(|ext| ShaderType::from_ext(ext).ok())
+            .ok_or_else(|| ShaderError::UnknownExtension(path.to_path_buf()))?;
+        let shader_src = std::fs::read_to_string(path).map_err(|source| ShaderError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        self.sources.push(ShaderSource {
+            stage: shader_type,
+            file: Some(path.to_path_buf()),
+            text: shader_src,
+        });
+        Ok(self)
+    }
+
+    // Attaches a source that didn't come from a file. Like attached files, the source is
+    // preprocessed and compiled when the program is linked
+    pub fn compile_shader(
         mut self,
         shader_src: &str,
         shader_type: ShaderType,
-    ) -> ShaderBuilder {
-        let shader = gl::CreateShader(shader_type.into());
-        let c_str_shader = CString::new(shader_src.as_bytes()).unwrap();
+    ) -> Result<ShaderBuilder, ShaderError> {
+        self.sources.push(ShaderSource {
+            stage: shader_type,
+            file: None,
+            text: shader_src.to_string(),
+        });
+        Ok(self)
+    }
+
+    // Resolves includes and defines of an attached source, returning the text to hand to the driver
+    fn preprocess_source(&mut self, index: usize) -> Result<(String, SourceMap), ShaderError> {
+        let source = &self.sources[index];
+        let mut sources = SourceMap::new();
+        let shader_src = preprocess::preprocess(
+            &source.text,
+            source.file.as_deref(),
+            &self.include_dirs,
+            &mut sources,
+        )?;
+        let shader_src = preprocess::inject_defines(&shader_src, &self.defines);
+        for path in sources.paths() {
+            if !self.dependencies.iter().any(|p| p == path) {
+                self.dependencies.push(path.to_path_buf());
+            }
+        }
+        Ok((shader_src, sources))
+    }
+
+    unsafe fn compile_source(
+        &mut self,
+        index: usize,
+        shader_src: &str,
+        sources: &SourceMap,
+    ) -> Result<(), ShaderError> {
+        let source = &self.sources[index];
+        let file = source.file.as_deref();
+
+        let shader = gl::CreateShader(source.stage.into());
+        // Track the shader right away so it is cleaned up even if compilation fails
+        self.shaders.0.push(shader);
+
+        // Interior NUL bytes can't be passed to the driver, so treat them as a compile failure
+        let c_str_shader = CString::new(shader_src.as_bytes()).map_err(|e| ShaderError::Compile {
+            stage: source.stage,
+            file: file.map(Path::to_path_buf),
+            log: e.to_string(),
+            diagnostics: vec![],
+        })?;
         gl::ShaderSource(shader, 1, &c_str_shader.as_ptr(), ptr::null());
         gl::CompileShader(shader);
 
-        if !self.check_shader_errors(shader) {
-            panic!("Shader failed to compile.");
+        if let Err(log) = check_shader_errors(shader) {
+            return Err(ShaderError::Compile {
+                stage: source.stage,
+                file: file.map(Path::to_path_buf),
+                diagnostics: diagnostics::parse_info_log(&log, sources),
+                log,
+            });
         }
 
-        self.shaders.push(shader);
-
-        self
+        Ok(())
     }
 
-    unsafe fn check_shader_errors(&self, shader_id: u32) -> bool {
-        let mut success = i32::from(gl::FALSE);
-        let mut info_log = Vec::with_capacity(512);
-        info_log.set_len(512 - 1);
-        gl::GetShaderiv(shader_id, gl::COMPILE_STATUS, &mut success);
-        if success != i32::from(gl::TRUE) {
-            gl::GetShaderInfoLog(
-                shader_id,
-                512,
-                ptr::null_mut(),
-                info_log.as_mut_ptr() as *mut gl::types::GLchar,
-            );
-            println!(
-                "ERROR::Shader Compilation Failed!\n{}",
-                String::from_utf8_lossy(&info_log)
-            );
-            return false;
-        }
-        true
-    }
-
-    unsafe fn check_linker_errors(&self) -> bool {
-        let mut success = i32::from(gl::FALSE);
-        let mut info_log = Vec::with_capacity(512);
-        info_log.set_len(512 - 1);
-        gl::GetProgramiv(self.program_id, gl::LINK_STATUS, &mut success);
-        if success != i32::from(gl::TRUE) {
-            gl::GetProgramInfoLog(
-                self.program_id,
-                512,
-                ptr::null_mut(),
-                info_log.as_mut_ptr() as *mut gl::types::GLchar,
-            );
-            println!(
-                "ERROR::SHADER::PROGRAM::COMPILATION_FAILED\n{}",
-                String::from_utf8_lossy(&info_log)
-            );
-            return false;
-        }
-        true
+    // Compiles every attached source and links them into a program
+    #[must_use = "The shader program is useless if not stored in a variable."]
+    pub unsafe fn link(self) -> Result<Shader, ShaderError> {
+        self.link_program().map(|(shader, _)| shader)
     }
 
+    // Like `link`, but the returned shader keeps track of the files it was built from so it can
+    // be rebuilt when any of them change
     #[must_use = "The shader program is useless if not stored in a variable."]
-    pub unsafe fn link(self) -> Shader {
-        for &shader in &self.shaders {
-            gl::AttachShader(self.program_id, shader);
-        }
-        gl::LinkProgram(self.program_id);
+    pub unsafe fn link_watched(self) -> Result<WatchedShader, ShaderError> {
+        let sources = self.sources.clone();
+        let include_dirs = self.include_dirs.clone();
+        let defines = self.defines.clone();
+        let cache_dir = self.cache_dir.clone();
+        let (shader, dependencies) = self.link_program()?;
+        Ok(WatchedShader::new(
+            shader,
+            sources,
+            include_dirs,
+            defines,
+            cache_dir,
+            dependencies,
+        ))
+    }
 
-        // todo:: use this to make safer abstraction
-        self.check_linker_errors();
+    // Links the program, returning it along with every file that was read to build it
+    unsafe fn link_program(mut self) -> Result<(Shader, Vec<PathBuf>), ShaderError> {
+        let preprocessed = (0..self.sources.len())
+            .map(|index| self.preprocess_source(index))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        for &shader in &self.shaders {
-            gl::DeleteShader(shader);
-        }
+        // A cached binary skips compilation entirely. If the driver rejects it, e.g. after a driver
+        // update, the program is left unlinked and is built from source below
+        let cache_path = self.cache_dir.as_ref().map(|dir| {
+            let texts = preprocessed.iter().map(|(text, _)| text.as_str());
+            binary_cache::entry_path(dir, &self.sources, &self.defines, texts)
+        });
+        let loaded = match &cache_path {
+            Some(path) => binary_cache::load(self.program.id(), path),
+            None => false,
+        };
+
+        if !loaded {
+            for (index, (shader_src, sources)) in preprocessed.iter().enumerate() {
+                self.compile_source(index, shader_src, sources)?;
+            }
 
-        Shader {
-            program_id: self.program_id,
+            let program_id = self.program.id();
+            for &shader in &self.shaders.0 {
+                gl::AttachShader(program_id, shader);
+            }
+            if cache_path.is_some() {
+                gl::ProgramParameteri(program_id, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as i32);
+            }
+            gl::LinkProgram(program_id);
+
+            check_linker_errors(program_id).map_err(|log| ShaderError::Link { log })?;
+
+            if let Some(path) = &cache_path {
+                binary_cache::store(program_id, path);
+            }
         }
+
+        let shader = Shader {
+            program: self.program,
+        };
+        Ok((shader, self.dependencies))
     }
 }
 
-impl ShaderUniform {
-    pub fn new(program: &Shader, uniform_name: &str) -> ShaderUniform {
-        let uniform_string = CString::new(uniform_name)
-            .expect("Could not convert uniform name to c_string");
+impl ShaderPermutations {
+    pub fn new(shader_paths: &[&str]) -> ShaderPermutations {
+        ShaderPermutations {
+            files: shader_paths.iter().map(|p| p.to_string()).collect(),
+            include_dirs: vec![],
+            variants: HashMap::new(),
+        }
+    }
 
-        let uniform_loc =
-            unsafe { gl::GetUniformLocation(program.program_id, uniform_string.as_ptr()) };
-        ShaderUniform {
-            program_id: program.program_id,
-            location: uniform_loc,
+    pub fn include_dir(mut self, dir: &str) -> ShaderPermutations {
+        self.include_dirs.push(dir.to_string());
+        self
+    }
+
+    // Returns the variant built with exactly the given defines, building it on first use.
+    // The order of the defines doesn't matter
+    pub unsafe fn get(&mut self, defines: &[(&str, &str)]) -> Result<&Shader, ShaderError> {
+        let key: BTreeMap<String, String> = defines
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        if !self.variants.contains_key(&key) {
+            let mut builder = ShaderBuilder::new();
+            for dir in &self.include_dirs {
+                builder = builder.include_dir(dir);
+            }
+            for (name, value) in &key {
+                builder = builder.define(name, value);
+            }
+            for file in &self.files {
+                builder = builder.attach_file(file)?;
+            }
+            let shader = builder.link_watched()?;
+            self.variants.insert(key.clone(), shader);
         }
+
+        Ok(&self.variants[&key])
     }
 
-    pub fn update1f(&self, value: f32) {
-        unsafe { gl::ProgramUniform1f(self.program_id, self.location, value) };
+    // Rebuilds every variant whose files changed since the last call. Returns true if any variant
+    // was replaced, in which case its uniforms have to be looked up and uploaded again
+    pub unsafe fn reload_if_changed(&mut self) -> bool {
+        let mut reloaded = false;
+        for variant in self.variants.values_mut() {
+            reloaded |= variant.reload_if_changed();
+        }
+        reloaded
     }
+}
 
-    pub fn update2f(&self, value: &[f32; 2]) {
-        let v0 = value[0];
-        let v1 = value[1];
-        unsafe { gl::ProgramUniform2f(self.program_id, self.location, v0, v1) };
+// The info log getters need a buffer large enough for the whole log, including the NUL terminator
+unsafe fn read_info_log(
+    object_id: u32,
+    get_iv: unsafe fn(u32, gl::types::GLenum, *mut i32),
+    get_log: unsafe fn(u32, i32, *mut i32, *mut gl::types::GLchar),
+) -> String {
+    let mut log_length = 0;
+    get_iv(object_id, gl::INFO_LOG_LENGTH, &mut log_length);
+    if log_length <= 0 {
+        return String::new();
     }
 
-    pub fn update3f(&self, value: &[f32; 3]) {
-        let v0 = value[0];
-        let v1 = value[1];
-        let v2 = value[2];
-        unsafe { gl::ProgramUniform3f(self.program_id, self.location, v0, v1, v2) };
+    let mut info_log = vec![0u8; log_length as usize];
+    let mut written = 0;
+    get_log(
+        object_id,
+        log_length,
+        &mut written,
+        info_log.as_mut_ptr() as *mut gl::types::GLchar,
+    );
+    info_log.truncate(written.max(0) as usize);
+    String::from_utf8_lossy(&info_log)
+        .trim_end_matches('\0')
+        .trim_end()
+        .to_string()
+}
+
+unsafe fn check_shader_errors(shader_id: u32) -> Result<(), String> {
+    let mut success = i32::from(gl::FALSE);
+    gl::GetShaderiv(shader_id, gl::COMPILE_STATUS, &mut success);
+    if success != i32::from(gl::TRUE) {
+        return Err(read_info_log(shader_id, gl::GetShaderiv, gl::GetShaderInfoLog));
     }
+    Ok(())
+}
 
-    pub fn update4f(&self, value: &[f32; 4]) {
-        let v0 = value[0];
-        let v1 = value[1];
-        let v2 = value[2];
-        let v3 = value[3];
-        unsafe { gl::ProgramUniform4f(self.program_id, self.location, v0, v1, v2, v3) };
+unsafe fn check_linker_errors(program_id: u32) -> Result<(), String> {
+    let mut success = i32::from(gl::FALSE);
+    gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut success);
+    if success != i32::from(gl::TRUE) {
+        return Err(read_info_log(program_id, gl::GetProgramiv, gl::GetProgramInfoLog));
     }
+    Ok(())
+}
 
-    pub fn updatefmat2(&self, value: &glm::Mat2, transpose: bool){
-        let mat_ptr = value.as_ptr();
+impl Drop for CompiledShaders {
+    // Shaders that are still attached to a program are only flagged for deletion, and go away
+    // together with the program
+    fn drop(&mut self) {
         unsafe {
-            gl::ProgramUniformMatrix2fv(
-                self.program_id,
-                self.location,
-                1,
-                transpose as u8,
-                mat_ptr
-            );
+            for &shader in &self.0 {
+                gl::DeleteShader(shader);
+            }
         }
     }
+}
 
-    pub fn updatefmat4(&self, value: &glm::Mat4, transpose: bool){
-        let mat_ptr = value.as_ptr();
-        unsafe {
-            gl::ProgramUniformMatrix4fv(
-                self.program_id,
-                self.location,
-                1,
-                transpose as u8,
-                mat_ptr
-            );
+impl ShaderUniform {
+    // Looks up `uniform_name` in `program`. Uniforms that aren't active in the program, because they
+    // are misspelled or were optimized out, are reported here and ignored when set
+    pub fn new(program: &Shader, uniform_name: &str) -> ShaderUniform {
+        let info = unsafe { reflect::find_uniform(program, uniform_name) };
+        let array_size = info.as_ref().map_or(1, |i| i.array_size.max(1) as usize);
+        let (location, gl_type) = match info {
+            Some(info) if info.location >= 0 => (info.location, Some(info.gl_type)),
+            Some(UniformInfo { block: Some(block), .. }) => {
+                println!(
+                    "WARNING: uniform `{}` is a member of block `{}` and can't be set directly",
+                    uniform_name, block
+                );
+                (-1, None)
+            }
+            _ => {
+                println!(
+                    "WARNING: uniform `{}` is not active in program {}; it is misspelled or was optimized out",
+                    uniform_name, program.program_id()
+                );
+                (-1, None)
+            }
+        };
+        ShaderUniform {
+            name: uniform_name.to_string(),
+            program_id: program.program_id(),
+            location,
+            gl_type,
+            array_size,
+            mismatch_reported: Cell::new(false),
         }
     }
 
+    // Looks the uniform up again in `program`, e.g. after the shader has been reloaded.
+    // Values have to be uploaded again afterwards, as they belonged to the old program
+    pub fn refresh(&mut self, program: &Shader) {
+        *self = ShaderUniform::new(program, &self.name);
+    }
+
+    // Uploads `value` if its type matches the type declared in the shader
+    pub fn set<T: UniformValue + ?Sized>(&self, value: &T) {
+        let gl_type = match self.gl_type {
+            Some(gl_type) => gl_type,
+            None => return,
+        };
+        if !T::accepts(gl_type) {
+            if !self.mismatch_reported.replace(true) {
+                println!(
+                    "WARNING: uniform `{}` is declared as {} but was set with a {}",
+                    self.name,
+                    reflect::glsl_type_name(gl_type),
+                    T::glsl_type()
+                );
+            }
+            return;
+        }
+        // Uploading past the end of an array is fine, the extra elements are dropped. Uploading
+        // several values to something that isn't an array is an error
+        if self.array_size == 1 && value.count() > 1 {
+            if !self.mismatch_reported.replace(true) {
+                println!(
+                    "WARNING: uniform `{}` is a single {} but was set with an array of {}",
+                    self.name,
+                    reflect::glsl_type_name(gl_type),
+                    value.count()
+                );
+            }
+            return;
+        }
+        unsafe { value.upload(self.program_id, self.location) };
+    }
 }
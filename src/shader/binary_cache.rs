@@ -0,0 +1,251 @@
+// On-disk cache of linked program binaries, so programs that haven't changed skip compilation.
+//
+// Entries are named `<program>-<contents>.bin`. The first hash identifies the program by its files
+// and defines, and the second covers the preprocessed sources and the driver that built them, since
+// a binary is only valid for the exact driver that produced it. Storing a new entry for a program
+// removes its older ones, so editing a shader doesn't pile up binaries. Each entry stores the
+// binary format as a little endian u32 followed by the binary itself. Any problem reading or
+// writing the cache just means the program is built from source.
+
+use gl::types::GLenum;
+use std::{
+    env, fs,
+    os::raw::c_void,
+    path::{Path, PathBuf},
+};
+
+use super::ShaderSource;
+use crate::util;
+
+// The per-user cache directory: $XDG_CACHE_HOME, %LOCALAPPDATA% on Windows, or else ~/.cache.
+// None if none of them are set, which turns caching off
+#[cfg(not(test))]
+pub fn default_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(base.join("gloom-rs").join("programs"))
+}
+
+// Unit tests always build from source, and leave the user's cache alone
+#[cfg(test)]
+pub fn default_dir() -> Option<PathBuf> {
+    None
+}
+
+// 64-bit FNV-1a, which unlike `DefaultHasher` is guaranteed to be stable between builds
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        // Separate consecutive fields so ("ab", "c") and ("a", "bc") hash differently
+        self.0 ^= 0xff;
+        self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+}
+
+// Identifies a program across edits to its files: the attached files by path, sources that didn't
+// come from a file by their text, and the defines. Paths are canonicalized, so the same file
+// reached through different relative paths or working directories gives the same key
+fn program_key(sources: &[ShaderSource], defines: &[(String, String)]) -> u64 {
+    let mut hash = Fnv1a::new();
+    for source in sources {
+        let stage: GLenum = source.stage.into();
+        hash.write(&stage.to_le_bytes());
+        match &source.file {
+            Some(file) => {
+                let file = fs::canonicalize(file).unwrap_or_else(|_| file.clone());
+                hash.write(file.to_string_lossy().as_bytes());
+            }
+            None => hash.write(source.text.as_bytes()),
+        }
+    }
+    for (name, value) in defines {
+        hash.write(name.as_bytes());
+        hash.write(value.as_bytes());
+    }
+    hash.0
+}
+
+// The file the program built from `sources` with `defines` is cached in. `preprocessed` holds the
+// text handed to the driver for each source
+pub unsafe fn entry_path<'a>(
+    dir: &Path,
+    sources: &[ShaderSource],
+    defines: &[(String, String)],
+    preprocessed: impl Iterator<Item = &'a str>,
+) -> PathBuf {
+    let mut hash = Fnv1a::new();
+    for name in &[gl::VENDOR, gl::RENDERER, gl::VERSION] {
+        hash.write(util::get_gl_string(*name).as_bytes());
+    }
+    for (source, text) in sources.iter().zip(preprocessed) {
+        let stage: GLenum = source.stage.into();
+        hash.write(&stage.to_le_bytes());
+        hash.write(text.as_bytes());
+    }
+    dir.join(format!("{:016x}-{:016x}.bin", program_key(sources, defines), hash.0))
+}
+
+// Removes the entries of the same program as `path` other than `path` itself. They were built from
+// earlier versions of the sources or by another driver. Undoing an edit or switching back to the
+// other driver makes one of them valid again, but rebuilding once then is cheaper than keeping a
+// binary around for every edit ever made
+fn remove_stale_entries(path: &Path) {
+    let name = path.file_name().and_then(|name| name.to_str());
+    let prefix = match name.and_then(|name| name.split_once('-')) {
+        Some((key, _)) => format!("{}-", key),
+        None => return,
+    };
+    let entries = match path.parent().map(fs::read_dir) {
+        Some(Ok(entries)) => entries,
+        _ => return,
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let other = entry.path();
+        let stale = other
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".bin"));
+        if stale && other != path {
+            let _ = fs::remove_file(&other);
+        }
+    }
+}
+
+unsafe fn supported_formats() -> Vec<GLenum> {
+    let mut count = 0;
+    gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut count);
+    if count <= 0 {
+        return vec![];
+    }
+    let mut formats = vec![0i32; count as usize];
+    gl::GetIntegerv(gl::PROGRAM_BINARY_FORMATS, formats.as_mut_ptr());
+    formats.into_iter().map(|f| f as GLenum).collect()
+}
+
+// Loads the binary at `path` into `program_id`. Returns true if the program is now linked
+pub unsafe fn load(program_id: u32, path: &Path) -> bool {
+    let entry = match fs::read(path) {
+        Ok(entry) if entry.len() > 4 => entry,
+        _ => return false,
+    };
+    let (format, binary) = entry.split_at(4);
+    let format = u32::from_le_bytes([format[0], format[1], format[2], format[3]]);
+    // An unknown format would raise a GL error rather than just failing to link
+    if !supported_formats().contains(&format) {
+        return false;
+    }
+
+    gl::ProgramBinary(
+        program_id,
+        format,
+        binary.as_ptr() as *const c_void,
+        binary.len() as i32,
+    );
+    let mut success = i32::from(gl::FALSE);
+    gl::GetProgramiv(program_id, gl::LINK_STATUS, &mut success);
+    if success != i32::from(gl::TRUE) {
+        println!("Cached program binary {} was rejected, rebuilding", path.display());
+        let _ = fs::remove_file(path);
+        return false;
+    }
+    true
+}
+
+// Writes the binary of the linked program `program_id` to `path`
+pub unsafe fn store(program_id: u32, path: &Path) {
+    let mut length = 0;
+    gl::GetProgramiv(program_id, gl::PROGRAM_BINARY_LENGTH, &mut length);
+    if length <= 0 {
+        return;
+    }
+
+    let mut binary = vec![0u8; length as usize];
+    let mut written = 0;
+    let mut format = 0;
+    gl::GetProgramBinary(
+        program_id,
+        length,
+        &mut written,
+        &mut format,
+        binary.as_mut_ptr() as *mut c_void,
+    );
+    binary.truncate(written.max(0) as usize);
+
+    let mut entry = format.to_le_bytes().to_vec();
+    entry.extend_from_slice(&binary);
+    // Write to a temporary file first, so a crash never leaves a truncated entry behind
+    let temp_path = path.with_extension("tmp");
+    let result = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&temp_path, &entry))
+        .and_then(|_| fs::rename(&temp_path, path));
+    match result {
+        Ok(()) => remove_stale_entries(path),
+        Err(e) => println!("WARNING: could not cache program binary {}: {}", path.display(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader::ShaderType;
+
+    fn file(stage: ShaderType, path: &str, text: &str) -> ShaderSource {
+        ShaderSource {
+            stage,
+            file: Some(PathBuf::from(path)),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn program_key_ignores_file_contents() {
+        let before = [file(ShaderType::Fragment, "shaders/simple.frag", "void main() {}")];
+        let after = [file(ShaderType::Fragment, "shaders/simple.frag", "void main() { }")];
+        assert_eq!(program_key(&before, &[]), program_key(&after, &[]));
+
+        let other = [file(ShaderType::Fragment, "shaders/mesh.frag", "void main() {}")];
+        assert_ne!(program_key(&before, &[]), program_key(&other, &[]));
+        let lit = [("LIT".to_string(), "1".to_string())];
+        assert_ne!(program_key(&before, &[]), program_key(&before, &lit));
+    }
+
+    #[test]
+    fn program_key_canonicalizes_paths() {
+        let direct = [file(ShaderType::Fragment, "shaders/simple.frag", "")];
+        let detour = [file(ShaderType::Fragment, "./shaders/../shaders/simple.frag", "")];
+        assert_eq!(program_key(&direct, &[]), program_key(&detour, &[]));
+    }
+
+    #[test]
+    fn stale_entries_are_removed() {
+        let dir = env::temp_dir().join(format!("gloom-rs-binary-cache-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in &["aaaa-0001.bin", "aaaa-0002.bin", "aaaa-0003.tmp", "bbbb-0001.bin"] {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+
+        remove_stale_entries(&dir.join("aaaa-0002.bin"));
+        let mut left: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(left, ["aaaa-0002.bin", "aaaa-0003.tmp", "bbbb-0001.bin"]);
+    }
+}
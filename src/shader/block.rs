@@ -0,0 +1,247 @@
+// Uniform buffer objects holding plain Rust structs laid out by the std140 rules.
+//
+// Structs meant for a uniform block are declared with `std140_block!`, which checks at compile time
+// that every field sits at the offset std140 assigns to it. If it doesn't, the build fails and the
+// fix is to reorder fields, or to fill the gap GLSL leaves with unused `f32` fields.
+//
+//     std140_block! {
+//         pub struct Lighting {
+//             pub light_position: glm::Vec3,
+//             pub intensity: f32,
+//             pub light_color: glm::Vec4,
+//         }
+//     }
+//
+// The struct can then be uploaded with a `UniformBlock<Lighting>` bound to `uniform Lighting { .. }`
+// in every program that uses it.
+
+use std::{ffi::CString, marker::PhantomData, mem, os::raw::c_void};
+
+use super::Shader;
+use crate::gl_object::Buffer;
+
+/// Types whose Rust memory layout matches their std140 layout.
+///
+/// # Safety
+/// `SIZE` must equal the number of bytes std140 reserves for the type, and the type's in-memory
+/// representation must be exactly what GLSL reads from those bytes
+pub unsafe trait Std140: Copy {
+    // Base alignment under std140
+    const ALIGN: usize;
+    // Size under std140, not counting padding that follows it
+    const SIZE: usize;
+}
+
+pub const fn align_up(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align) * align
+}
+
+macro_rules! std140_primitive {
+    ($($t:ty => $align:expr, $size:expr;)*) => {
+        $(
+            unsafe impl Std140 for $t {
+                const ALIGN: usize = $align;
+                const SIZE: usize = $size;
+            }
+        )*
+    };
+}
+
+// Matrices other than mat4 have columns that std140 pads out to a vec4, so their nalgebra
+// counterparts don't fit and are left out on purpose
+std140_primitive! {
+    f32 => 4, 4;
+    i32 => 4, 4;
+    u32 => 4, 4;
+    glm::Vec2 => 8, 8;
+    glm::Vec3 => 16, 12;
+    glm::Vec4 => 16, 16;
+    glm::IVec2 => 8, 8;
+    glm::IVec3 => 16, 12;
+    glm::IVec4 => 16, 16;
+    glm::UVec2 => 8, 8;
+    glm::UVec3 => 16, 12;
+    glm::UVec4 => 16, 16;
+    glm::Mat4 => 16, 64;
+}
+
+// Array elements are rounded up to a multiple of a vec4. `std140_block!` rejects arrays whose Rust
+// stride doesn't match, such as `[f32; 4]`
+unsafe impl<T: Std140, const N: usize> Std140 for [T; N] {
+    const ALIGN: usize = align_up(T::ALIGN, 16);
+    const SIZE: usize = N * align_up(T::SIZE, 16);
+}
+
+// Declares a `#[repr(C)]` struct that implements `Std140`, failing to compile if any field is not
+// at its std140 offset. Structs declared this way can be nested in each other
+#[macro_export]
+macro_rules! std140_block {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident : $t:ty),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy)]
+        #[repr(C, align(16))]
+        $vis struct $name {
+            $($field_vis $field: $t),*
+        }
+
+        unsafe impl $crate::shader::block::Std140 for $name {
+            const ALIGN: usize = 16;
+            const SIZE: usize = std::mem::size_of::<$name>();
+        }
+
+        const _: () = {
+            use $crate::shader::block::{align_up, Std140};
+            let mut offset = 0;
+            $(
+                offset = align_up(offset, <$t as Std140>::ALIGN);
+                assert!(
+                    offset == std::mem::offset_of!($name, $field),
+                    concat!(
+                        "field `", stringify!($field), "` of `", stringify!($name),
+                        "` is not at its std140 offset; reorder the fields or pad with f32 fields before it"
+                    )
+                );
+                assert!(
+                    std::mem::size_of::<$t>() == <$t as Std140>::SIZE,
+                    concat!(
+                        "field `", stringify!($field), "` of `", stringify!($name),
+                        "` has a different size in Rust than in std140"
+                    )
+                );
+                offset += <$t as Std140>::SIZE;
+            )*
+            assert!(
+                align_up(offset, 16) == std::mem::size_of::<$name>(),
+                concat!("`", stringify!($name), "` does not end where std140 expects it to")
+            );
+        };
+    };
+}
+
+// A uniform buffer holding one `T`, attached to a fixed binding point
+pub struct UniformBlock<T: Std140> {
+    buffer: Buffer,
+    binding: u32,
+    _contents: PhantomData<T>,
+}
+
+impl<T: Std140> UniformBlock<T> {
+    // Creates the buffer with `value` as its initial contents, and attaches it to `binding`
+    pub unsafe fn new(binding: u32, value: &T) -> UniformBlock<T> {
+        let buffer = Buffer::new();
+        gl::NamedBufferData(
+            buffer.id(),
+            mem::size_of::<T>() as isize,
+            value as *const T as *const c_void,
+            gl::DYNAMIC_DRAW,
+        );
+        gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, buffer.id());
+        UniformBlock {
+            buffer,
+            binding,
+            _contents: PhantomData,
+        }
+    }
+
+    pub unsafe fn update(&self, value: &T) {
+        gl::NamedBufferSubData(
+            self.buffer.id(),
+            0,
+            mem::size_of::<T>() as isize,
+            value as *const T as *const c_void,
+        );
+    }
+
+    // Makes the block called `block_name` in `program` read from this buffer. Blocks that are
+    // missing, or that have a different size than `T`, are reported and left alone
+    pub unsafe fn bind_to(&self, program: &Shader, block_name: &str) {
+        let name = CString::new(block_name).expect("Could not convert block name to c_string");
+        let index = gl::GetUniformBlockIndex(program.program_id(), name.as_ptr());
+        if index == gl::INVALID_INDEX {
+            println!(
+                "WARNING: uniform block `{}` is not active in program {}",
+                block_name, program.program_id()
+            );
+            return;
+        }
+
+        let mut data_size = 0;
+        gl::GetActiveUniformBlockiv(
+            program.program_id(),
+            index,
+            gl::UNIFORM_BLOCK_DATA_SIZE,
+            &mut data_size,
+        );
+        // Drivers may leave out the padding at the end of the block, which `T` always includes
+        if align_up(data_size as usize, 16) != mem::size_of::<T>() {
+            println!(
+                "WARNING: uniform block `{}` is {} bytes in program {}, but the buffer holds {} bytes",
+                block_name,
+                data_size,
+                program.program_id(),
+                mem::size_of::<T>()
+            );
+            return;
+        }
+
+        gl::UniformBlockBinding(program.program_id(), index, self.binding);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::{align_of, offset_of, size_of};
+
+    use super::*;
+
+    crate::std140_block! {
+        struct Light {
+            direction: glm::Vec3,
+            intensity: f32,
+            color: glm::Vec4,
+            range: f32,
+        }
+    }
+
+    crate::std140_block! {
+        struct Lights {
+            // Followed by a gap, as the array is aligned to a vec4
+            count: u32,
+            lights: [Light; 2],
+            ambient: glm::Vec3,
+        }
+    }
+
+    #[test]
+    fn float_packs_after_vec3() {
+        // A vec3 only takes 12 bytes, and std140 lets a float fill the rest of its 16
+        assert_eq!(offset_of!(Light, direction), 0);
+        assert_eq!(offset_of!(Light, intensity), 12);
+        assert_eq!(offset_of!(Light, color), 16);
+        assert_eq!(offset_of!(Light, range), 32);
+        assert_eq!(size_of::<Light>(), 48);
+        assert_eq!(align_of::<Light>(), 16);
+        assert_eq!(<Light as Std140>::SIZE, 48);
+    }
+
+    #[test]
+    fn nested_blocks_and_arrays() {
+        assert_eq!(offset_of!(Lights, lights), 16);
+        assert_eq!(<[Light; 2] as Std140>::SIZE, 96);
+        assert_eq!(offset_of!(Lights, ambient), 112);
+        assert_eq!(size_of::<Lights>(), 128);
+    }
+
+    #[test]
+    fn align_up_rounds_to_multiples() {
+        assert_eq!(align_up(0, 16), 0);
+        assert_eq!(align_up(1, 16), 16);
+        assert_eq!(align_up(16, 16), 16);
+        assert_eq!(align_up(140, 4), 140);
+    }
+}
@@ -0,0 +1,172 @@
+// Running compute programs and sharing data with them through shader storage buffers.
+//
+// A compute program is built like any other, by attaching a `.comp` file to a `ShaderBuilder`.
+// Writes made by a dispatch are only guaranteed to be visible to later GL commands after a
+// `memory_barrier` covering the way the data will be read.
+
+use gl::types::GLbitfield;
+use std::{ffi::CString, marker::PhantomData, mem, os::raw::c_void};
+
+use super::Shader;
+use crate::gl_object::Buffer;
+
+// Which kinds of later reads a memory barrier should make compute writes visible to. Barriers
+// without a constant here can be built from their bits, e.g. `MemoryBarrier(gl::BUFFER_UPDATE_BARRIER_BIT)`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryBarrier(pub GLbitfield);
+
+impl MemoryBarrier {
+    // For storage buffers that a later draw or dispatch reads in its shaders
+    pub const SHADER_STORAGE: MemoryBarrier = MemoryBarrier(gl::SHADER_STORAGE_BARRIER_BIT);
+}
+
+pub unsafe fn memory_barrier(barrier: MemoryBarrier) {
+    gl::MemoryBarrier(barrier.0);
+}
+
+impl Shader {
+    // The `local_size_x/y/z` the compute program was declared with
+    pub unsafe fn work_group_size(&self) -> [u32; 3] {
+        let mut size = [0i32; 3];
+        gl::GetProgramiv(self.program_id(), gl::COMPUTE_WORK_GROUP_SIZE, size.as_mut_ptr());
+        [size[0] as u32, size[1] as u32, size[2] as u32]
+    }
+
+    // Runs the compute program on the given number of work groups
+    pub unsafe fn dispatch(&self, x: u32, y: u32, z: u32) {
+        self.activate();
+        gl::DispatchCompute(x, y, z);
+    }
+
+    // Runs enough work groups to cover at least `x * y * z` invocations, e.g. one per pixel of an
+    // image. The shader has to ignore the invocations that fall outside of the range
+    pub unsafe fn dispatch_invocations(&self, x: u32, y: u32, z: u32) {
+        let [size_x, size_y, size_z] = self.work_group_size();
+        self.dispatch(
+            x.div_ceil(size_x.max(1)),
+            y.div_ceil(size_y.max(1)),
+            z.div_ceil(size_z.max(1)),
+        );
+    }
+}
+
+// A shader storage buffer holding an array of `T`, attached to a fixed binding point.
+// `T` has to be laid out like the GLSL side expects under std430, so use `#[repr(C)]` types
+pub struct StorageBuffer<T: Copy> {
+    buffer: Buffer,
+    binding: u32,
+    len: usize,
+    _contents: PhantomData<T>,
+}
+
+impl<T: Copy> StorageBuffer<T> {
+    // Creates the buffer with `data` as its initial contents, and attaches it to `binding`
+    pub unsafe fn new(binding: u32, data: &[T]) -> StorageBuffer<T> {
+        let buffer = Buffer::new();
+        gl::NamedBufferData(
+            buffer.id(),
+            mem::size_of_val(data) as isize,
+            data.as_ptr() as *const c_void,
+            gl::DYNAMIC_COPY,
+        );
+        gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, buffer.id());
+        StorageBuffer {
+            buffer,
+            binding,
+            len: data.len(),
+            _contents: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    // Overwrites the elements starting at `first` with `data`
+    pub unsafe fn update(&self, first: usize, data: &[T]) {
+        assert!(first + data.len() <= self.len, "write past the end of a storage buffer");
+        gl::NamedBufferSubData(
+            self.buffer.id(),
+            (first * mem::size_of::<T>()) as isize,
+            mem::size_of_val(data) as isize,
+            data.as_ptr() as *const c_void,
+        );
+    }
+
+    // Makes the storage block called `block_name` in `program` use this buffer
+    pub unsafe fn bind_to(&self, program: &Shader, block_name: &str) {
+        let name = CString::new(block_name).expect("Could not convert block name to c_string");
+        let index = gl::GetProgramResourceIndex(
+            program.program_id(),
+            gl::SHADER_STORAGE_BLOCK,
+            name.as_ptr(),
+        );
+        if index == gl::INVALID_INDEX {
+            println!(
+                "WARNING: storage block `{}` is not active in program {}",
+                block_name, program.program_id()
+            );
+            return;
+        }
+        gl::ShaderStorageBlockBinding(program.program_id(), index, self.binding);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::with_test_context;
+    use crate::shader::{ShaderBuilder, ShaderType};
+
+    const DOUBLE_AND_ADD_INDEX: &str = "#version 430 core
+layout(local_size_x = 4) in;
+layout(std430) buffer Values {
+    uint values[];
+};
+void main()
+{
+    uint i = gl_GlobalInvocationID.x;
+    if (i < values.length()) {
+        values[i] = values[i] * 2u + i;
+    }
+}
+";
+
+    // Copies the buffer back to the CPU
+    unsafe fn read(values: &StorageBuffer<u32>) -> Vec<u32> {
+        memory_barrier(MemoryBarrier(gl::BUFFER_UPDATE_BARRIER_BIT));
+        let mut data = vec![0; values.len()];
+        gl::GetNamedBufferSubData(
+            values.buffer.id(),
+            0,
+            mem::size_of_val(&data[..]) as isize,
+            data.as_mut_ptr() as *mut c_void,
+        );
+        data
+    }
+
+    #[test]
+    fn storage_buffer_round_trip() {
+        with_test_context(|| unsafe {
+            let shader = ShaderBuilder::new()
+                .compile_shader(DOUBLE_AND_ADD_INDEX, ShaderType::Compute)
+                .and_then(|b| b.link())
+                .unwrap_or_else(|e| panic!("{}", e));
+            assert_eq!(shader.work_group_size(), [4, 1, 1]);
+
+            // Ten values don't fill the last work group, which the shader has to skip
+            let values = StorageBuffer::new(3, &(0..10).collect::<Vec<u32>>());
+            values.bind_to(&shader, "Values");
+            assert_eq!(values.len(), 10);
+
+            shader.dispatch_invocations(values.len() as u32, 1, 1);
+            assert_eq!(read(&values), (0..10).map(|i| i * 3).collect::<Vec<u32>>());
+
+            values.update(8, &[100, 200]);
+            shader.dispatch_invocations(values.len() as u32, 1, 1);
+            let mut expected: Vec<u32> = (0..10).map(|i| i * 7).collect();
+            expected[8..].copy_from_slice(&[208, 409]);
+            assert_eq!(read(&values), expected);
+        });
+    }
+}
@@ -0,0 +1,321 @@
+// Turns the info logs handed back by the driver into compiler-style diagnostics.
+//
+// Every vendor formats its messages a little differently, but they all refer to a location as a
+// pair of (source string number, line). The source string number is whatever was last set with
+// `#line <line> <number>`, or 0 if no such directive was seen, so a `SourceMap` keeps track of which
+// file each number refers to.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: Option<PathBuf>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: String,
+    // The offending line of source, if it could be found
+    excerpt: Option<String>,
+}
+
+struct SourceFile {
+    path: Option<PathBuf>,
+    text: String,
+}
+
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+// A location as reported by the driver, before it has been mapped back to a file
+struct RawMessage<'a> {
+    severity: Severity,
+    source: Option<u32>,
+    line: Option<u32>,
+    column: Option<u32>,
+    message: &'a str,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap::default()
+    }
+
+    // Registers a source and returns the source string number it should be referred to by
+    pub fn add(&mut self, path: Option<&Path>, text: &str) -> u32 {
+        self.files.push(SourceFile {
+            path: path.map(Path::to_path_buf),
+            text: text.to_string(),
+        });
+        (self.files.len() - 1) as u32
+    }
+
+    pub fn path(&self, source: u32) -> Option<&Path> {
+        self.files.get(source as usize)?.path.as_deref()
+    }
+
+    // Every file that contributed to the source, including the ones pulled in by `#include`
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().filter_map(|f| f.path.as_deref())
+    }
+
+    fn line(&self, source: u32, line: u32) -> Option<&str> {
+        let file = self.files.get(source as usize)?;
+        file.text.lines().nth(line.checked_sub(1)? as usize)
+    }
+}
+
+impl Severity {
+    fn parse(word: &str) -> Option<Severity> {
+        match word.trim().to_ascii_lowercase().as_str() {
+            "error" | "fatal error" => Some(Severity::Error),
+            "warning" => Some(Severity::Warning),
+            "note" | "info" => Some(Severity::Note),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+// Splits a leading run of digits off of `s`
+fn split_number(s: &str) -> Option<(u32, &str)> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let number = s[..end].parse().ok()?;
+    Some((number, &s[end..]))
+}
+
+// Mesa: `0:12(3): error: message`
+fn parse_mesa(line: &str) -> Option<RawMessage<'_>> {
+    let (source, rest) = split_number(line)?;
+    let (line_number, rest) = split_number(rest.strip_prefix(':')?)?;
+    let (column, rest) = split_number(rest.strip_prefix('(')?)?;
+    let rest = rest.strip_prefix("):")?;
+    let (severity, message) = rest.split_once(':')?;
+    Some(RawMessage {
+        severity: Severity::parse(severity)?,
+        source: Some(source),
+        line: Some(line_number),
+        column: Some(column),
+        message: message.trim(),
+    })
+}
+
+// NVIDIA: `0(12) : error C0000: message`
+fn parse_nvidia(line: &str) -> Option<RawMessage<'_>> {
+    let (source, rest) = split_number(line)?;
+    let (line_number, rest) = split_number(rest.strip_prefix('(')?)?;
+    let rest = rest.strip_prefix(')')?.trim_start().strip_prefix(':')?;
+    let (kind, message) = rest.split_once(':')?;
+    // `kind` is the severity followed by a vendor error code
+    let severity = kind.split_whitespace().next()?;
+    Some(RawMessage {
+        severity: Severity::parse(severity)?,
+        source: Some(source),
+        line: Some(line_number),
+        column: None,
+        message: message.trim(),
+    })
+}
+
+// AMD and Intel on Windows: `ERROR: 0:12: message`
+fn parse_amd(line: &str) -> Option<RawMessage<'_>> {
+    let (severity, rest) = line.split_once(':')?;
+    let severity = Severity::parse(severity)?;
+    let (source, rest) = split_number(rest.trim_start())?;
+    let (line_number, rest) = split_number(rest.strip_prefix(':')?)?;
+    let message = rest.strip_prefix(':')?;
+    Some(RawMessage {
+        severity,
+        source: Some(source),
+        line: Some(line_number),
+        column: None,
+        message: message.trim(),
+    })
+}
+
+// Anything else, such as most linker messages. These carry no location
+fn parse_unlocated(line: &str) -> RawMessage<'_> {
+    let (severity, message) = match line.split_once(':') {
+        Some((prefix, rest)) => match Severity::parse(prefix) {
+            Some(severity) => (severity, rest.trim()),
+            None => (Severity::Note, line),
+        },
+        None => (Severity::Note, line),
+    };
+    RawMessage {
+        severity,
+        source: None,
+        line: None,
+        column: None,
+        message,
+    }
+}
+
+pub fn parse_info_log(log: &str, sources: &SourceMap) -> Vec<Diagnostic> {
+    log.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let raw = parse_mesa(line)
+                .or_else(|| parse_nvidia(line))
+                .or_else(|| parse_amd(line))
+                .unwrap_or_else(|| parse_unlocated(line));
+            let excerpt = match (raw.source, raw.line) {
+                (Some(source), Some(line)) => sources.line(source, line).map(str::to_string),
+                _ => None,
+            };
+            Diagnostic {
+                severity: raw.severity,
+                file: raw.source.and_then(|s| sources.path(s)).map(Path::to_path_buf),
+                line: raw.line,
+                column: raw.column,
+                message: raw.message.to_string(),
+                excerpt,
+            }
+        })
+        .collect()
+}
+
+impl fmt::Display for Diagnostic {
+    // Formats the diagnostic the same way rustc does:
+    //
+    //     error: 'foo' : undeclared identifier
+    //       --> ./shaders/simple.frag:12:5
+    //        |
+    //     12 |     color = foo;
+    //        |     ^
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)?;
+
+        let line = match self.line {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+        let gutter = " ".repeat(line.to_string().len());
+        let file = self
+            .file
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "<source>".to_string());
+        match self.column {
+            Some(column) => write!(f, "\n{}--> {}:{}:{}", gutter, file, line, column)?,
+            None => write!(f, "\n{}--> {}:{}", gutter, file, line)?,
+        }
+
+        if let Some(excerpt) = &self.excerpt {
+            write!(f, "\n{} |", gutter)?;
+            write!(f, "\n{} | {}", line, excerpt.trim_end())?;
+            if let Some(column) = self.column {
+                let indent = " ".repeat(column.saturating_sub(1) as usize);
+                write!(f, "\n{} | {}^", gutter, indent)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fragment shader that includes common.glsl as source string 1
+    fn sources() -> SourceMap {
+        let mut sources = SourceMap::new();
+        sources.add(
+            Some(Path::new("shaders/simple.frag")),
+            "#version 430 core\nout vec4 color;\nvoid main()\n{\n    color = foo;\n}\n",
+        );
+        sources.add(
+            Some(Path::new("shaders/common.glsl")),
+            "uniform float iTime;\nuniform vec2 screenDims\nuniform mat4 view;\n",
+        );
+        sources
+    }
+
+    fn parse_one(log: &str) -> Diagnostic {
+        let mut diagnostics = parse_info_log(log, &sources());
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+        diagnostics.remove(0)
+    }
+
+    #[test]
+    fn mesa() {
+        let diagnostic = parse_one("0:5(13): error: `foo' undeclared\n");
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.file.as_deref(), Some(Path::new("shaders/simple.frag")));
+        assert_eq!(diagnostic.line, Some(5));
+        assert_eq!(diagnostic.column, Some(13));
+        assert_eq!(diagnostic.message, "`foo' undeclared");
+        assert_eq!(diagnostic.excerpt.as_deref(), Some("    color = foo;"));
+    }
+
+    #[test]
+    fn nvidia() {
+        let diagnostic = parse_one("1(3) : error C0000: syntax error, unexpected reserved word \"uniform\"");
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.file.as_deref(), Some(Path::new("shaders/common.glsl")));
+        assert_eq!(diagnostic.line, Some(3));
+        assert_eq!(diagnostic.column, None);
+        assert_eq!(diagnostic.message, "syntax error, unexpected reserved word \"uniform\"");
+        assert_eq!(diagnostic.excerpt.as_deref(), Some("uniform mat4 view;"));
+    }
+
+    #[test]
+    fn amd() {
+        let diagnostic = parse_one("WARNING: 1:2: 'screenDims' : missing semicolon");
+        assert_eq!(diagnostic.severity, Severity::Warning);
+        assert_eq!(diagnostic.file.as_deref(), Some(Path::new("shaders/common.glsl")));
+        assert_eq!(diagnostic.line, Some(2));
+        assert_eq!(diagnostic.column, None);
+        assert_eq!(diagnostic.message, "'screenDims' : missing semicolon");
+        assert_eq!(diagnostic.excerpt.as_deref(), Some("uniform vec2 screenDims"));
+    }
+
+    #[test]
+    fn without_location() {
+        let diagnostic = parse_one("ERROR: 1 compilation errors.  No code generated.");
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.file, None);
+        assert_eq!(diagnostic.line, None);
+        assert_eq!(diagnostic.column, None);
+        assert_eq!(diagnostic.message, "1 compilation errors.  No code generated.");
+        assert_eq!(diagnostic.excerpt, None);
+    }
+
+    #[test]
+    fn unknown_source_number() {
+        let diagnostic = parse_one("7:1(1): error: something went wrong");
+        assert_eq!(diagnostic.file, None);
+        assert_eq!(diagnostic.line, Some(1));
+        assert_eq!(diagnostic.excerpt, None);
+    }
+
+    #[test]
+    fn display_points_at_column() {
+        let diagnostic = parse_one("0:5(13): error: `foo' undeclared");
+        assert_eq!(
+            diagnostic.to_string(),
+            "error: `foo' undeclared\n --> shaders/simple.frag:5:13\n  |\n5 |     color = foo;\n  |             ^"
+        );
+    }
+}
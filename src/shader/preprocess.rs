@@ -0,0 +1,276 @@
+// Resolves `#include "file"` directives and injects `#define`s before a source is handed to the
+// driver.
+//
+// Included files are spliced in place, surrounded by `#line` directives so that the driver reports
+// locations in terms of the original files. Every file gets its own source string number, which is
+// recorded in the `SourceMap` so diagnostics can be traced back to the right path.
+
+use std::path::{Path, PathBuf};
+
+use super::{diagnostics::SourceMap, ShaderError};
+
+struct Preprocessor<'a> {
+    include_dirs: &'a [PathBuf],
+    sources: &'a mut SourceMap,
+    // Canonical paths of the files currently being expanded, outermost first
+    stack: Vec<PathBuf>,
+}
+
+// Returns the quoted or bracketed path of an `#include` directive, if `line` is one
+fn include_target(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start();
+    let rest = rest.strip_prefix("include")?.trim();
+    if let Some(quoted) = rest.strip_prefix('"') {
+        return quoted.strip_suffix('"');
+    }
+    rest.strip_prefix('<')?.strip_suffix('>')
+}
+
+impl<'a> Preprocessor<'a> {
+    // Looks for `target` next to the including file first, then in each include directory in order
+    fn resolve(&self, target: &str, including_file: Option<&Path>) -> Option<PathBuf> {
+        let local_dir = including_file.and_then(Path::parent);
+        local_dir
+            .into_iter()
+            .chain(self.include_dirs.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(target))
+            .find(|candidate| candidate.is_file())
+    }
+
+    fn expand(&mut self, text: &str, file: Option<&Path>) -> Result<String, ShaderError> {
+        let source_number = self.sources.add(file, text);
+        let mut output = String::with_capacity(text.len());
+        // Included files need to tell the driver which file it is now reading. The root file is
+        // always source 0, which is what the driver assumes anyway
+        if source_number != 0 {
+            output.push_str(&format!("#line 1 {}\n", source_number));
+        }
+
+        let line_count = text.lines().count();
+        for (index, line) in text.lines().enumerate() {
+            let target = match include_target(line) {
+                Some(target) => target,
+                None => {
+                    output.push_str(line);
+                    output.push('\n');
+                    continue;
+                }
+            };
+
+            let included = self.resolve(target, file).ok_or_else(|| ShaderError::IncludeNotFound {
+                file: file.map(Path::to_path_buf),
+                line: index as u32 + 1,
+                include: target.to_string(),
+            })?;
+            let canonical = included.canonicalize().map_err(|source| ShaderError::Io {
+                path: included.clone(),
+                source,
+            })?;
+            if self.stack.contains(&canonical) {
+                let mut chain = self.stack.clone();
+                chain.push(canonical);
+                return Err(ShaderError::IncludeCycle(chain));
+            }
+            let included_text = std::fs::read_to_string(&included).map_err(|source| ShaderError::Io {
+                path: included.clone(),
+                source,
+            })?;
+
+            self.stack.push(canonical);
+            output.push_str(&self.expand(&included_text, Some(&included))?);
+            // Resume counting from the line after the directive. When nothing follows, the
+            // including file's own includer emits the directive that matters next
+            if index + 1 < line_count {
+                output.push_str(&format!("#line {} {}\n", index + 2, source_number));
+            }
+            self.stack.pop();
+        }
+
+        Ok(output)
+    }
+}
+
+// Expands every `#include` in `text`. `file` is the path `text` was read from, if any, and is
+// registered as source string number 0
+pub fn preprocess(
+    text: &str,
+    file: Option<&Path>,
+    include_dirs: &[PathBuf],
+    sources: &mut SourceMap,
+) -> Result<String, ShaderError> {
+    let mut preprocessor = Preprocessor {
+        include_dirs,
+        sources,
+        stack: vec![],
+    };
+    if let Some(canonical) = file.and_then(|f| f.canonicalize().ok()) {
+        preprocessor.stack.push(canonical);
+    }
+    preprocessor.expand(text, file)
+}
+
+// Inserts a `#define` for each (name, value) pair right after the `#version` line, which has to stay
+// the first statement of the source. A `#line` directive afterwards keeps line numbers intact
+pub fn inject_defines(text: &str, defines: &[(String, String)]) -> String {
+    if defines.is_empty() {
+        return text.to_string();
+    }
+
+    let version_line = text
+        .lines()
+        .position(|line| line.trim_start().starts_with("#version"));
+    let split = match version_line {
+        Some(index) => index + 1,
+        None => 0,
+    };
+
+    let mut output = String::with_capacity(text.len());
+    for line in text.lines().take(split) {
+        output.push_str(line);
+        output.push('\n');
+    }
+    for (name, value) in defines {
+        output.push_str(&format!("#define {} {}\n", name, value));
+    }
+    output.push_str(&format!("#line {} 0\n", split + 1));
+    for line in text.lines().skip(split) {
+        output.push_str(line);
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A directory under the system temp dir that is removed again when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path = std::env::temp_dir().join(format!("gloom-rs-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn write(&self, name: &str, text: &str) -> PathBuf {
+            let path = self.0.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, text).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn run(root: &Path, include_dirs: &[PathBuf]) -> (Result<String, ShaderError>, SourceMap) {
+        let mut sources = SourceMap::new();
+        let text = std::fs::read_to_string(root).unwrap();
+        let result = preprocess(&text, Some(root), include_dirs, &mut sources);
+        (result, sources)
+    }
+
+    #[test]
+    fn nested_includes() {
+        let dir = TempDir::new("nested-includes");
+        let root = dir.write("main.frag", "#version 430\n#include \"a.glsl\"\nvoid main() {}\n");
+        let a = dir.write("a.glsl", "float a;\n#include <lib/b.glsl>\n");
+        let b = dir.write("include/lib/b.glsl", "float b;\n");
+
+        let (result, sources) = run(&root, &[dir.0.join("include")]);
+        assert_eq!(
+            result.unwrap(),
+            "#version 430\n\
+             #line 1 1\nfloat a;\n\
+             #line 1 2\nfloat b;\n\
+             #line 3 0\nvoid main() {}\n"
+        );
+        assert_eq!(sources.path(0), Some(root.as_path()));
+        assert_eq!(sources.path(1), Some(a.as_path()));
+        assert_eq!(sources.path(2), Some(b.as_path()));
+    }
+
+    #[test]
+    fn include_at_end_of_file() {
+        let dir = TempDir::new("include-at-end");
+        let root = dir.write("main.frag", "#include \"a.glsl\"\n");
+        dir.write("a.glsl", "float a;\n");
+
+        let (result, _) = run(&root, &[]);
+        assert_eq!(result.unwrap(), "#line 1 1\nfloat a;\n");
+    }
+
+    #[test]
+    fn include_not_found() {
+        let dir = TempDir::new("include-not-found");
+        let root = dir.write("main.frag", "#version 430\n#include \"a.glsl\"\n");
+        let a = dir.write("a.glsl", "float a;\n\n  #include \"missing.glsl\"\n");
+
+        match run(&root, &[]).0 {
+            Err(ShaderError::IncludeNotFound { file, line, include }) => {
+                assert_eq!(file, Some(a));
+                assert_eq!(line, 3);
+                assert_eq!(include, "missing.glsl");
+            }
+            other => panic!("expected IncludeNotFound, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn include_cycle() {
+        let dir = TempDir::new("include-cycle");
+        let root = dir.write("main.frag", "#include \"a.glsl\"\n");
+        let a = dir.write("a.glsl", "#include \"b.glsl\"\n");
+        let b = dir.write("b.glsl", "#include \"a.glsl\"\n");
+
+        match run(&root, &[]).0 {
+            Err(ShaderError::IncludeCycle(chain)) => {
+                let expected: Vec<PathBuf> = [&root, &a, &b, &a]
+                    .iter()
+                    .map(|path| path.canonicalize().unwrap())
+                    .collect();
+                assert_eq!(chain, expected);
+            }
+            other => panic!("expected IncludeCycle, got {:?}", other),
+        }
+    }
+
+    fn defines(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn defines_follow_version() {
+        let text = "// A comment\n#version 430 core\nout vec4 color;\n";
+        assert_eq!(
+            inject_defines(text, &defines(&[("LIT", "1"), ("SAMPLES", "4")])),
+            "// A comment\n#version 430 core\n\
+             #define LIT 1\n#define SAMPLES 4\n\
+             #line 3 0\nout vec4 color;\n"
+        );
+    }
+
+    #[test]
+    fn defines_without_version() {
+        let text = "float a;\nfloat b;\n";
+        assert_eq!(
+            inject_defines(text, &defines(&[("LIT", "1")])),
+            "#define LIT 1\n#line 1 0\nfloat a;\nfloat b;\n"
+        );
+    }
+
+    #[test]
+    fn no_defines_leaves_source_alone() {
+        let text = "#version 430 core\nout vec4 color;";
+        assert_eq!(inject_defines(text, &[]), text);
+    }
+}
@@ -0,0 +1,396 @@
+// Introspection of a linked program: which uniforms, vertex attributes and buffer blocks are active,
+// and what types the shader expects them to have.
+//
+// Everything is queried through the program interface API (OpenGL 4.3). The UNIFORM interface
+// enumerates the same resources as GL_ACTIVE_UNIFORMS, and PROGRAM_INPUT the same as
+// GL_ACTIVE_ATTRIBUTES for programs with a vertex stage.
+
+use gl::types::GLenum;
+use std::{convert::TryFrom, ffi::CString, fmt};
+
+use super::Shader;
+
+#[derive(Clone, Debug)]
+pub struct UniformInfo {
+    pub name: String,
+    pub gl_type: GLenum,
+    pub array_size: i32,
+    // -1 for uniforms that live in a block, as those have no location of their own
+    pub location: i32,
+    // Name of the uniform block this uniform is a member of, if any
+    pub block: Option<String>,
+    // Byte offset within the block, or -1 outside of blocks
+    pub offset: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct AttributeInfo {
+    pub name: String,
+    pub gl_type: GLenum,
+    pub array_size: i32,
+    // -1 for built-ins such as gl_VertexID
+    pub location: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct BlockInfo {
+    pub name: String,
+    pub binding: i32,
+    pub data_size: i32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ProgramReflection {
+    pub uniforms: Vec<UniformInfo>,
+    pub attributes: Vec<AttributeInfo>,
+    pub uniform_blocks: Vec<BlockInfo>,
+    pub storage_blocks: Vec<BlockInfo>,
+}
+
+unsafe fn resource_count(program_id: u32, interface: GLenum) -> u32 {
+    let mut count = 0;
+    gl::GetProgramInterfaceiv(program_id, interface, gl::ACTIVE_RESOURCES, &mut count);
+    count.max(0) as u32
+}
+
+// Queries `props` of a single resource, in the same order
+unsafe fn resource_props<const N: usize>(
+    program_id: u32,
+    interface: GLenum,
+    index: u32,
+    props: [GLenum; N],
+) -> [i32; N] {
+    let mut values = [0; N];
+    gl::GetProgramResourceiv(
+        program_id,
+        interface,
+        index,
+        N as i32,
+        props.as_ptr(),
+        N as i32,
+        std::ptr::null_mut(),
+        values.as_mut_ptr(),
+    );
+    values
+}
+
+unsafe fn resource_name(program_id: u32, interface: GLenum, index: u32) -> String {
+    let [name_length] = resource_props(program_id, interface, index, [gl::NAME_LENGTH]);
+    if name_length <= 0 {
+        return String::new();
+    }
+    let mut name = vec![0u8; name_length as usize];
+    let mut written = 0;
+    gl::GetProgramResourceName(
+        program_id,
+        interface,
+        index,
+        name_length,
+        &mut written,
+        name.as_mut_ptr() as *mut gl::types::GLchar,
+    );
+    name.truncate(written.max(0) as usize);
+    String::from_utf8_lossy(&name).to_string()
+}
+
+unsafe fn blocks(program_id: u32, interface: GLenum) -> Vec<BlockInfo> {
+    (0..resource_count(program_id, interface))
+        .map(|index| {
+            let [binding, data_size] = resource_props(
+                program_id,
+                interface,
+                index,
+                [gl::BUFFER_BINDING, gl::BUFFER_DATA_SIZE],
+            );
+            BlockInfo {
+                name: resource_name(program_id, interface, index),
+                binding,
+                data_size,
+            }
+        })
+        .collect()
+}
+
+unsafe fn uniform_info(program_id: u32, index: u32) -> UniformInfo {
+    let [gl_type, array_size, location, block_index, offset] = resource_props(
+        program_id,
+        gl::UNIFORM,
+        index,
+        [gl::TYPE, gl::ARRAY_SIZE, gl::LOCATION, gl::BLOCK_INDEX, gl::OFFSET],
+    );
+    UniformInfo {
+        name: resource_name(program_id, gl::UNIFORM, index),
+        gl_type: gl_type as GLenum,
+        array_size,
+        location,
+        // The block index is -1 for uniforms in the default block
+        block: u32::try_from(block_index)
+            .ok()
+            .map(|i| resource_name(program_id, gl::UNIFORM_BLOCK, i)),
+        offset,
+    }
+}
+
+// Looks up a single uniform without reflecting the whole program. Returns None if the uniform isn't
+// active, either because the name is wrong or because the driver optimized it out.
+//
+// Elements of arrays such as `lights[2]` have locations of their own, but only the array as a
+// whole is a resource, so the type and size are taken from `lights[0]`
+pub unsafe fn find_uniform(program: &Shader, name: &str) -> Option<UniformInfo> {
+    let resource = match array_element(name) {
+        Some((base, _)) => format!("{}[0]", base),
+        None => name.to_string(),
+    };
+    let resource_cstr = CString::new(resource).ok()?;
+    let index = gl::GetProgramResourceIndex(program.program_id(), gl::UNIFORM, resource_cstr.as_ptr());
+    if index == gl::INVALID_INDEX {
+        return None;
+    }
+
+    let mut info = uniform_info(program.program_id(), index);
+    if let Some((_, element)) = array_element(name) {
+        if element >= info.array_size {
+            return None;
+        }
+        info.location = program.get_uniform_location(name);
+        info.name = name.to_string();
+    }
+    Some(info)
+}
+
+// Splits `lights[2]` into `lights` and 2
+fn array_element(name: &str) -> Option<(&str, i32)> {
+    let (base, index) = name.strip_suffix(']')?.rsplit_once('[')?;
+    Some((base, index.parse().ok()?))
+}
+
+// Strips the `[0]` drivers append to the names of array uniforms and attributes
+pub fn base_name(name: &str) -> &str {
+    name.strip_suffix("[0]").unwrap_or(name)
+}
+
+// The name as it would be declared in GLSL, e.g. `lights[4]` for an array of four
+fn declared_name(name: &str, array_size: i32) -> String {
+    if array_size > 1 {
+        format!("{}[{}]", base_name(name), array_size)
+    } else {
+        name.to_string()
+    }
+}
+
+impl ProgramReflection {
+    pub unsafe fn new(program: &Shader) -> ProgramReflection {
+        let program_id = program.program_id();
+        let uniform_blocks = blocks(program_id, gl::UNIFORM_BLOCK);
+        let storage_blocks = blocks(program_id, gl::SHADER_STORAGE_BLOCK);
+
+        let uniforms = (0..resource_count(program_id, gl::UNIFORM))
+            .map(|index| uniform_info(program_id, index))
+            .collect();
+
+        let attributes = (0..resource_count(program_id, gl::PROGRAM_INPUT))
+            .map(|index| {
+                let [gl_type, array_size, location] = resource_props(
+                    program_id,
+                    gl::PROGRAM_INPUT,
+                    index,
+                    [gl::TYPE, gl::ARRAY_SIZE, gl::LOCATION],
+                );
+                AttributeInfo {
+                    name: resource_name(program_id, gl::PROGRAM_INPUT, index),
+                    gl_type: gl_type as GLenum,
+                    array_size,
+                    location,
+                }
+            })
+            .collect();
+
+        ProgramReflection {
+            uniforms,
+            attributes,
+            uniform_blocks,
+            storage_blocks,
+        }
+    }
+}
+
+// The GLSL spelling of a type enum as returned by the reflection queries
+pub fn glsl_type_name(gl_type: GLenum) -> &'static str {
+    match gl_type {
+        gl::FLOAT => "float",
+        gl::FLOAT_VEC2 => "vec2",
+        gl::FLOAT_VEC3 => "vec3",
+        gl::FLOAT_VEC4 => "vec4",
+        gl::DOUBLE => "double",
+        gl::DOUBLE_VEC2 => "dvec2",
+        gl::DOUBLE_VEC3 => "dvec3",
+        gl::DOUBLE_VEC4 => "dvec4",
+        gl::INT => "int",
+        gl::INT_VEC2 => "ivec2",
+        gl::INT_VEC3 => "ivec3",
+        gl::INT_VEC4 => "ivec4",
+        gl::UNSIGNED_INT => "uint",
+        gl::UNSIGNED_INT_VEC2 => "uvec2",
+        gl::UNSIGNED_INT_VEC3 => "uvec3",
+        gl::UNSIGNED_INT_VEC4 => "uvec4",
+        gl::BOOL => "bool",
+        gl::BOOL_VEC2 => "bvec2",
+        gl::BOOL_VEC3 => "bvec3",
+        gl::BOOL_VEC4 => "bvec4",
+        gl::FLOAT_MAT2 => "mat2",
+        gl::FLOAT_MAT3 => "mat3",
+        gl::FLOAT_MAT4 => "mat4",
+        gl::FLOAT_MAT2x3 => "mat2x3",
+        gl::FLOAT_MAT2x4 => "mat2x4",
+        gl::FLOAT_MAT3x2 => "mat3x2",
+        gl::FLOAT_MAT3x4 => "mat3x4",
+        gl::FLOAT_MAT4x2 => "mat4x2",
+        gl::FLOAT_MAT4x3 => "mat4x3",
+        gl::SAMPLER_1D => "sampler1D",
+        gl::SAMPLER_2D => "sampler2D",
+        gl::SAMPLER_3D => "sampler3D",
+        gl::SAMPLER_CUBE => "samplerCube",
+        gl::SAMPLER_2D_SHADOW => "sampler2DShadow",
+        gl::SAMPLER_2D_ARRAY => "sampler2DArray",
+        gl::SAMPLER_CUBE_SHADOW => "samplerCubeShadow",
+        gl::SAMPLER_CUBE_MAP_ARRAY => "samplerCubeArray",
+        gl::SAMPLER_2D_MULTISAMPLE => "sampler2DMS",
+        gl::INT_SAMPLER_2D => "isampler2D",
+        gl::UNSIGNED_INT_SAMPLER_2D => "usampler2D",
+        gl::IMAGE_2D => "image2D",
+        gl::IMAGE_3D => "image3D",
+        gl::IMAGE_CUBE => "imageCube",
+        gl::IMAGE_2D_ARRAY => "image2DArray",
+        gl::IMAGE_BUFFER => "imageBuffer",
+        gl::INT_IMAGE_2D => "iimage2D",
+        gl::UNSIGNED_INT_IMAGE_2D => "uimage2D",
+        _ => "<unknown>",
+    }
+}
+
+pub fn is_sampler(gl_type: GLenum) -> bool {
+    matches!(
+        gl_type,
+        gl::SAMPLER_1D
+            | gl::SAMPLER_2D
+            | gl::SAMPLER_3D
+            | gl::SAMPLER_CUBE
+            | gl::SAMPLER_1D_SHADOW
+            | gl::SAMPLER_2D_SHADOW
+            | gl::SAMPLER_1D_ARRAY
+            | gl::SAMPLER_2D_ARRAY
+            | gl::SAMPLER_1D_ARRAY_SHADOW
+            | gl::SAMPLER_2D_ARRAY_SHADOW
+            | gl::SAMPLER_2D_MULTISAMPLE
+            | gl::SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::SAMPLER_CUBE_SHADOW
+            | gl::SAMPLER_BUFFER
+            | gl::SAMPLER_2D_RECT
+            | gl::SAMPLER_2D_RECT_SHADOW
+            | gl::SAMPLER_CUBE_MAP_ARRAY
+            | gl::SAMPLER_CUBE_MAP_ARRAY_SHADOW
+            | gl::INT_SAMPLER_1D
+            | gl::INT_SAMPLER_2D
+            | gl::INT_SAMPLER_3D
+            | gl::INT_SAMPLER_CUBE
+            | gl::INT_SAMPLER_1D_ARRAY
+            | gl::INT_SAMPLER_2D_ARRAY
+            | gl::INT_SAMPLER_2D_MULTISAMPLE
+            | gl::INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::INT_SAMPLER_BUFFER
+            | gl::INT_SAMPLER_2D_RECT
+            | gl::INT_SAMPLER_CUBE_MAP_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_1D
+            | gl::UNSIGNED_INT_SAMPLER_2D
+            | gl::UNSIGNED_INT_SAMPLER_3D
+            | gl::UNSIGNED_INT_SAMPLER_CUBE
+            | gl::UNSIGNED_INT_SAMPLER_1D_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE
+            | gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_BUFFER
+            | gl::UNSIGNED_INT_SAMPLER_2D_RECT
+            | gl::UNSIGNED_INT_SAMPLER_CUBE_MAP_ARRAY
+    )
+}
+
+pub fn is_image(gl_type: GLenum) -> bool {
+    matches!(
+        gl_type,
+        gl::IMAGE_1D
+            | gl::IMAGE_2D
+            | gl::IMAGE_3D
+            | gl::IMAGE_2D_RECT
+            | gl::IMAGE_CUBE
+            | gl::IMAGE_BUFFER
+            | gl::IMAGE_1D_ARRAY
+            | gl::IMAGE_2D_ARRAY
+            | gl::IMAGE_CUBE_MAP_ARRAY
+            | gl::IMAGE_2D_MULTISAMPLE
+            | gl::IMAGE_2D_MULTISAMPLE_ARRAY
+            | gl::INT_IMAGE_1D
+            | gl::INT_IMAGE_2D
+            | gl::INT_IMAGE_3D
+            | gl::INT_IMAGE_2D_RECT
+            | gl::INT_IMAGE_CUBE
+            | gl::INT_IMAGE_BUFFER
+            | gl::INT_IMAGE_1D_ARRAY
+            | gl::INT_IMAGE_2D_ARRAY
+            | gl::INT_IMAGE_CUBE_MAP_ARRAY
+            | gl::INT_IMAGE_2D_MULTISAMPLE
+            | gl::INT_IMAGE_2D_MULTISAMPLE_ARRAY
+            | gl::UNSIGNED_INT_IMAGE_1D
+            | gl::UNSIGNED_INT_IMAGE_2D
+            | gl::UNSIGNED_INT_IMAGE_3D
+            | gl::UNSIGNED_INT_IMAGE_2D_RECT
+            | gl::UNSIGNED_INT_IMAGE_CUBE
+            | gl::UNSIGNED_INT_IMAGE_BUFFER
+            | gl::UNSIGNED_INT_IMAGE_1D_ARRAY
+            | gl::UNSIGNED_INT_IMAGE_2D_ARRAY
+            | gl::UNSIGNED_INT_IMAGE_CUBE_MAP_ARRAY
+            | gl::UNSIGNED_INT_IMAGE_2D_MULTISAMPLE
+            | gl::UNSIGNED_INT_IMAGE_2D_MULTISAMPLE_ARRAY
+    )
+}
+
+impl fmt::Display for ProgramReflection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Attributes:")?;
+        for a in &self.attributes {
+            writeln!(
+                f,
+                "  layout(location = {}) {} {}",
+                a.location,
+                glsl_type_name(a.gl_type),
+                declared_name(&a.name, a.array_size)
+            )?;
+        }
+        writeln!(f, "Uniforms:")?;
+        for u in &self.uniforms {
+            match &u.block {
+                Some(block) => writeln!(
+                    f,
+                    "  {}.{}: {} (offset {})",
+                    block,
+                    declared_name(&u.name, u.array_size),
+                    glsl_type_name(u.gl_type),
+                    u.offset
+                )?,
+                None => writeln!(
+                    f,
+                    "  layout(location = {}) {} {}",
+                    u.location,
+                    glsl_type_name(u.gl_type),
+                    declared_name(&u.name, u.array_size)
+                )?,
+            }
+        }
+        for b in &self.uniform_blocks {
+            writeln!(f, "Uniform block {} (binding {}, {} bytes)", b.name, b.binding, b.data_size)?;
+        }
+        for b in &self.storage_blocks {
+            writeln!(f, "Storage block {} (binding {}, {} bytes)", b.name, b.binding, b.data_size)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,322 @@
+// Values that can be uploaded to a uniform through `ShaderUniform::set`.
+//
+// Each value knows which GLSL types it can be uploaded to, so a mismatch between what the
+// application sends and what the shader declares is caught before it reaches the driver.
+// Scalars, vectors and matrices are taken as nalgebra-glm types, and any slice or array of those
+// uploads to a GLSL array.
+
+use gl::types::GLenum;
+
+use super::reflect;
+
+pub trait UniformValue {
+    // Whether the value can be uploaded to a uniform of the given reflected type
+    fn accepts(gl_type: GLenum) -> bool;
+    // The GLSL type the value corresponds to, used in diagnostics
+    fn glsl_type() -> &'static str;
+    // The number of array elements the value covers
+    fn count(&self) -> usize {
+        1
+    }
+    unsafe fn upload(&self, program_id: u32, location: i32);
+}
+
+// A single element of a uniform, e.g. one `vec3`. Slices of elements upload as GLSL arrays
+pub trait UniformElement: Sized {
+    fn accepts(gl_type: GLenum) -> bool;
+    fn glsl_type() -> &'static str;
+    unsafe fn upload_slice(program_id: u32, location: i32, values: &[Self]);
+}
+
+// Binds a sampler uniform to a texture unit, i.e. the `n` in `gl::ActiveTexture(gl::TEXTURE0 + n)`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct TextureUnit(pub u32);
+
+// Binds an image uniform to an image unit, as passed to `gl::BindImageTexture`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct ImageUnit(pub u32);
+
+impl<T: UniformElement> UniformValue for T {
+    fn accepts(gl_type: GLenum) -> bool {
+        T::accepts(gl_type)
+    }
+
+    fn glsl_type() -> &'static str {
+        T::glsl_type()
+    }
+
+    unsafe fn upload(&self, program_id: u32, location: i32) {
+        T::upload_slice(program_id, location, std::slice::from_ref(self));
+    }
+}
+
+impl<T: UniformElement> UniformValue for [T] {
+    fn accepts(gl_type: GLenum) -> bool {
+        T::accepts(gl_type)
+    }
+
+    fn glsl_type() -> &'static str {
+        T::glsl_type()
+    }
+
+    fn count(&self) -> usize {
+        self.len()
+    }
+
+    unsafe fn upload(&self, program_id: u32, location: i32) {
+        T::upload_slice(program_id, location, self);
+    }
+}
+
+impl<T: UniformElement, const N: usize> UniformValue for [T; N] {
+    fn accepts(gl_type: GLenum) -> bool {
+        T::accepts(gl_type)
+    }
+
+    fn glsl_type() -> &'static str {
+        T::glsl_type()
+    }
+
+    fn count(&self) -> usize {
+        N
+    }
+
+    unsafe fn upload(&self, program_id: u32, location: i32) {
+        T::upload_slice(program_id, location, self);
+    }
+}
+
+// Implements `UniformElement` for a type whose memory layout matches what `$upload` expects
+macro_rules! uniform_element {
+    ($t:ty, $gl_type:expr, $upload:ident) => {
+        impl UniformElement for $t {
+            fn accepts(gl_type: GLenum) -> bool {
+                gl_type == $gl_type
+            }
+            fn glsl_type() -> &'static str {
+                reflect::glsl_type_name($gl_type)
+            }
+            unsafe fn upload_slice(program_id: u32, location: i32, values: &[Self]) {
+                gl::$upload(program_id, location, values.len() as i32, values.as_ptr() as *const _);
+            }
+        }
+    };
+    ($t:ty, $gl_type:expr, $upload:ident, matrix) => {
+        impl UniformElement for $t {
+            fn accepts(gl_type: GLenum) -> bool {
+                gl_type == $gl_type
+            }
+            fn glsl_type() -> &'static str {
+                reflect::glsl_type_name($gl_type)
+            }
+            unsafe fn upload_slice(program_id: u32, location: i32, values: &[Self]) {
+                // nalgebra matrices are column major, just like GLSL
+                gl::$upload(
+                    program_id,
+                    location,
+                    values.len() as i32,
+                    gl::FALSE,
+                    values.as_ptr() as *const _,
+                );
+            }
+        }
+    };
+    // GLSL booleans are uploaded through the integer functions
+    ($t:ty, $gl_type:expr, $upload:ident, bool) => {
+        impl UniformElement for $t {
+            fn accepts(gl_type: GLenum) -> bool {
+                gl_type == $gl_type
+            }
+            fn glsl_type() -> &'static str {
+                reflect::glsl_type_name($gl_type)
+            }
+            unsafe fn upload_slice(program_id: u32, location: i32, values: &[Self]) {
+                let ints: Vec<i32> = values
+                    .iter()
+                    .flat_map(|v| v.iter().map(|&b| b as i32))
+                    .collect();
+                gl::$upload(program_id, location, values.len() as i32, ints.as_ptr());
+            }
+        }
+    };
+}
+
+uniform_element!(f32, gl::FLOAT, ProgramUniform1fv);
+uniform_element!(glm::Vec2, gl::FLOAT_VEC2, ProgramUniform2fv);
+uniform_element!(glm::Vec3, gl::FLOAT_VEC3, ProgramUniform3fv);
+uniform_element!(glm::Vec4, gl::FLOAT_VEC4, ProgramUniform4fv);
+
+uniform_element!(i32, gl::INT, ProgramUniform1iv);
+uniform_element!(glm::IVec2, gl::INT_VEC2, ProgramUniform2iv);
+uniform_element!(glm::IVec3, gl::INT_VEC3, ProgramUniform3iv);
+uniform_element!(glm::IVec4, gl::INT_VEC4, ProgramUniform4iv);
+
+uniform_element!(u32, gl::UNSIGNED_INT, ProgramUniform1uiv);
+uniform_element!(glm::UVec2, gl::UNSIGNED_INT_VEC2, ProgramUniform2uiv);
+uniform_element!(glm::UVec3, gl::UNSIGNED_INT_VEC3, ProgramUniform3uiv);
+uniform_element!(glm::UVec4, gl::UNSIGNED_INT_VEC4, ProgramUniform4uiv);
+
+uniform_element!(glm::BVec1, gl::BOOL, ProgramUniform1iv, bool);
+uniform_element!(glm::BVec2, gl::BOOL_VEC2, ProgramUniform2iv, bool);
+uniform_element!(glm::BVec3, gl::BOOL_VEC3, ProgramUniform3iv, bool);
+uniform_element!(glm::BVec4, gl::BOOL_VEC4, ProgramUniform4iv, bool);
+
+uniform_element!(glm::Mat2, gl::FLOAT_MAT2, ProgramUniformMatrix2fv, matrix);
+uniform_element!(glm::Mat3, gl::FLOAT_MAT3, ProgramUniformMatrix3fv, matrix);
+uniform_element!(glm::Mat4, gl::FLOAT_MAT4, ProgramUniformMatrix4fv, matrix);
+
+// nalgebra names matrices rows x columns while GLSL names them columns x rows, so the
+// dimensions are swapped: a GLSL `mat2x3` has two columns of three rows, which is a `glm::Mat3x2`
+uniform_element!(glm::Mat3x2, gl::FLOAT_MAT2x3, ProgramUniformMatrix2x3fv, matrix);
+uniform_element!(glm::Mat4x2, gl::FLOAT_MAT2x4, ProgramUniformMatrix2x4fv, matrix);
+uniform_element!(glm::Mat2x3, gl::FLOAT_MAT3x2, ProgramUniformMatrix3x2fv, matrix);
+uniform_element!(glm::Mat4x3, gl::FLOAT_MAT3x4, ProgramUniformMatrix3x4fv, matrix);
+uniform_element!(glm::Mat2x4, gl::FLOAT_MAT4x2, ProgramUniformMatrix4x2fv, matrix);
+uniform_element!(glm::Mat3x4, gl::FLOAT_MAT4x3, ProgramUniformMatrix4x3fv, matrix);
+
+// A plain `bool` is the most natural way to pass a flag, so it gets its own impl
+impl UniformElement for bool {
+    fn accepts(gl_type: GLenum) -> bool {
+        gl_type == gl::BOOL
+    }
+
+    fn glsl_type() -> &'static str {
+        "bool"
+    }
+
+    unsafe fn upload_slice(program_id: u32, location: i32, values: &[Self]) {
+        let ints: Vec<i32> = values.iter().map(|&b| b as i32).collect();
+        gl::ProgramUniform1iv(program_id, location, values.len() as i32, ints.as_ptr());
+    }
+}
+
+impl UniformElement for TextureUnit {
+    fn accepts(gl_type: GLenum) -> bool {
+        reflect::is_sampler(gl_type)
+    }
+
+    fn glsl_type() -> &'static str {
+        "sampler"
+    }
+
+    unsafe fn upload_slice(program_id: u32, location: i32, values: &[Self]) {
+        // Units are tiny, so reinterpreting them as the signed ints GL wants is lossless
+        gl::ProgramUniform1iv(program_id, location, values.len() as i32, values.as_ptr() as *const i32);
+    }
+}
+
+impl UniformElement for ImageUnit {
+    fn accepts(gl_type: GLenum) -> bool {
+        reflect::is_image(gl_type)
+    }
+
+    fn glsl_type() -> &'static str {
+        "image"
+    }
+
+    unsafe fn upload_slice(program_id: u32, location: i32, values: &[Self]) {
+        gl::ProgramUniform1iv(program_id, location, values.len() as i32, values.as_ptr() as *const i32);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::headless::with_test_context;
+    use crate::shader::{Shader, ShaderBuilder, ShaderType, ShaderUniform};
+
+    const VERTEX: &str = "#version 430 core
+void main()
+{
+    gl_Position = vec4(0.0, 0.0, 0.0, 1.0);
+}
+";
+
+    const LIGHTS: &str = "#version 430 core
+uniform float scale;
+uniform vec3 lights[4];
+out vec4 color;
+void main()
+{
+    color = vec4(scale * (lights[0] + lights[1] + lights[2] + lights[3]), 1.0);
+}
+";
+
+    const ARRAYS: &str = "#version 430 core
+uniform ivec2 offsets[3];
+uniform mat3 rotations[2];
+out vec4 color;
+void main()
+{
+    vec2 offset = vec2(offsets[0] + offsets[1] + offsets[2]);
+    color = vec4(rotations[0] * rotations[1] * vec3(offset, 1.0), 1.0);
+}
+";
+
+    unsafe fn program(fragment: &str) -> Shader {
+        ShaderBuilder::new()
+            .compile_shader(VERTEX, ShaderType::Vertex)
+            .and_then(|b| b.compile_shader(fragment, ShaderType::Fragment))
+            .and_then(|b| b.link())
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    unsafe fn read_vec3(shader: &Shader, name: &str) -> [f32; 3] {
+        let mut value = [0.0; 3];
+        gl::GetUniformfv(shader.program_id(), shader.get_uniform_location(name), value.as_mut_ptr());
+        value
+    }
+
+    #[test]
+    fn set_array_element() {
+        with_test_context(|| unsafe {
+            let shader = program(LIGHTS);
+            let light = ShaderUniform::new(&shader, "lights[2]");
+            assert_eq!(light.gl_type, Some(gl::FLOAT_VEC3));
+
+            light.set(&glm::vec3(1.0, 2.0, 3.0));
+            assert_eq!(read_vec3(&shader, "lights[2]"), [1.0, 2.0, 3.0]);
+            assert_eq!(read_vec3(&shader, "lights[0]"), [0.0, 0.0, 0.0]);
+        });
+    }
+
+    #[test]
+    fn mismatched_and_missing_uniforms_are_ignored() {
+        with_test_context(|| unsafe {
+            let shader = program(LIGHTS);
+            // Past the end of the array
+            assert_eq!(ShaderUniform::new(&shader, "lights[4]").gl_type, None);
+            assert_eq!(ShaderUniform::new(&shader, "misspelled").gl_type, None);
+
+            let light = ShaderUniform::new(&shader, "lights[1]");
+            light.set(&0.5f32);
+            assert!(light.mismatch_reported.get());
+            assert_eq!(read_vec3(&shader, "lights[1]"), [0.0, 0.0, 0.0]);
+        });
+    }
+
+    #[test]
+    fn set_arrays_from_non_zero_index() {
+        with_test_context(|| unsafe {
+            let shader = program(ARRAYS);
+
+            // A slice starting at an element fills the elements after it
+            ShaderUniform::new(&shader, "offsets[1]").set(&[glm::vec2(1, 2), glm::vec2(3, 4)]);
+            let mut offsets = [0; 6];
+            for (i, offset) in offsets.chunks_mut(2).enumerate() {
+                let location = shader.get_uniform_location(&format!("offsets[{}]", i));
+                gl::GetUniformiv(shader.program_id(), location, offset.as_mut_ptr());
+            }
+            assert_eq!(offsets, [0, 0, 1, 2, 3, 4]);
+
+            let rotation = glm::rotation(1.0, &glm::vec3(0.0, 0.0, 1.0));
+            let rotation = glm::mat4_to_mat3(&rotation);
+            ShaderUniform::new(&shader, "rotations[1]").set(&rotation);
+            let mut values = [0.0; 9];
+            let location = shader.get_uniform_location("rotations[1]");
+            gl::GetUniformfv(shader.program_id(), location, values.as_mut_ptr());
+            assert_eq!(&values[..], rotation.as_slice());
+        });
+    }
+}
@@ -0,0 +1,121 @@
+// Rebuilds a shader program whenever one of the files it was built from changes on disk.
+//
+// Changes are detected by polling modification times, which is cheap enough to do from the render
+// loop. A rebuild that fails leaves the last working program in place, so a typo in a shader being
+// edited doesn't take down the running application.
+
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use super::{Shader, ShaderBuilder, ShaderSource};
+
+// How often the files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct WatchedShader {
+    shader: Shader,
+    sources: Vec<ShaderSource>,
+    include_dirs: Vec<PathBuf>,
+    defines: Vec<(String, String)>,
+    cache_dir: Option<PathBuf>,
+    // Every file the program was built from, along with its modification time when it was read
+    watched: Vec<(PathBuf, Option<SystemTime>)>,
+    last_poll: Instant,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn stamp(paths: Vec<PathBuf>) -> Vec<(PathBuf, Option<SystemTime>)> {
+    paths
+        .into_iter()
+        .map(|path| {
+            let time = modified(&path);
+            (path, time)
+        })
+        .collect()
+}
+
+impl WatchedShader {
+    pub(super) fn new(
+        shader: Shader,
+        sources: Vec<ShaderSource>,
+        include_dirs: Vec<PathBuf>,
+        defines: Vec<(String, String)>,
+        cache_dir: Option<PathBuf>,
+        dependencies: Vec<PathBuf>,
+    ) -> WatchedShader {
+        WatchedShader {
+            shader,
+            sources,
+            include_dirs,
+            defines,
+            cache_dir,
+            watched: stamp(dependencies),
+            last_poll: Instant::now(),
+        }
+    }
+
+    // Rebuilds the program if any of its files changed since the last call. Returns true if the
+    // program was replaced, in which case every `ShaderUniform` has to be refreshed and re-uploaded.
+    // Must be called from the thread that owns the GL context
+    pub unsafe fn reload_if_changed(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+
+        let changed = self
+            .watched
+            .iter()
+            .any(|(path, time)| modified(path) != *time);
+        if !changed {
+            return false;
+        }
+
+        // Don't retry a broken build until the files change again
+        for (path, time) in self.watched.iter_mut() {
+            *time = modified(path);
+        }
+
+        match self.rebuild() {
+            Ok((shader, dependencies)) => {
+                // The old program is deleted as it is dropped here
+                self.shader = shader;
+                self.watched = stamp(dependencies);
+                println!("Reloaded shader program {}", self.shader.program_id());
+                true
+            }
+            Err(e) => {
+                eprintln!("Shader reload failed, keeping the previous program:\n{}", e);
+                false
+            }
+        }
+    }
+
+    unsafe fn rebuild(&self) -> Result<(Shader, Vec<PathBuf>), super::ShaderError> {
+        let mut builder = ShaderBuilder::new();
+        builder.include_dirs = self.include_dirs.clone();
+        builder.defines = self.defines.clone();
+        builder.cache_dir = self.cache_dir.clone();
+        for source in &self.sources {
+            builder = match &source.file {
+                Some(file) => builder.attach_file(&file.to_string_lossy())?,
+                None => builder.compile_shader(&source.text, source.stage)?,
+            };
+        }
+        builder.link_program()
+    }
+}
+
+impl Deref for WatchedShader {
+    type Target = Shader;
+
+    fn deref(&self) -> &Shader {
+        &self.shader
+    }
+}
@@ -0,0 +1,53 @@
+// Draws a cubemap as the backdrop of the scene.
+//
+// The sky is drawn at the maximum depth with only the rotation of the view, so it never gets
+// closer as the camera moves. Draw it after the opaque geometry, so only the pixels nothing else
+// covered run its fragment shader, and before anything blended, which has to be blended with it.
+
+use crate::gl_object::VertexArray;
+use crate::shader::uniform::TextureUnit;
+use crate::shader::{Shader, ShaderBuilder, ShaderError, ShaderUniform};
+use crate::texture::Cubemap;
+
+pub struct Skybox {
+    shader: Shader,
+    // The vertices are generated in the shader, but core profiles still need a vertex array bound
+    vao: VertexArray,
+    inverse_view_projection: ShaderUniform,
+    cubemap: ShaderUniform,
+}
+
+impl Skybox {
+    pub unsafe fn new() -> Result<Skybox, ShaderError> {
+        let shader = ShaderBuilder::new()
+            .attach_file("./shaders/skybox.vert")?
+            .attach_file("./shaders/skybox.frag")?
+            .link()?;
+        Ok(Skybox {
+            inverse_view_projection: ShaderUniform::new(&shader, "inverseViewProjection"),
+            cubemap: ShaderUniform::new(&shader, "skybox"),
+            vao: VertexArray::new(),
+            shader,
+        })
+    }
+
+    // Where the camera is doesn't matter, only which way it looks
+    pub unsafe fn draw(&self, cubemap: &Cubemap, view_projection: &glm::Mat4) {
+        self.inverse_view_projection.set(&glm::inverse(view_projection));
+        cubemap.bind_to(&self.cubemap, TextureUnit(0));
+
+        // The sky sits exactly on the far plane, which the default depth test of LESS would reject
+        // against a cleared depth buffer. It also shouldn't hide anything drawn after it
+        let mut depth_func = 0;
+        gl::GetIntegerv(gl::DEPTH_FUNC, &mut depth_func);
+        gl::DepthFunc(gl::LEQUAL);
+        gl::DepthMask(gl::FALSE);
+
+        self.shader.activate();
+        self.vao.bind();
+        gl::DrawArrays(gl::TRIANGLES, 0, 3);
+
+        gl::DepthMask(gl::TRUE);
+        gl::DepthFunc(depth_func as u32);
+    }
+}
@@ -0,0 +1,426 @@
+// 2D textures and cubemaps loaded from image files through the `image` crate.
+//
+// Images are stored top row first, while OpenGL expects the bottom row first, so 2D textures are
+// flipped on upload. A texture coordinate of (0, 0) therefore samples the bottom left corner of
+// the image. Cubemap faces are the exception: their conventions already expect the top row first.
+
+use std::{
+    fmt,
+    os::raw::c_void,
+    path::{Path, PathBuf},
+};
+
+use gl::types::{GLenum, GLint};
+use image::{DynamicImage, GenericImageView};
+
+use crate::gl_object::Texture;
+use crate::shader::compute::{memory_barrier, MemoryBarrier};
+use crate::shader::uniform::{ImageUnit, TextureUnit};
+use crate::shader::{ShaderBuilder, ShaderError, ShaderUniform};
+
+// How the color values of an image are encoded. Colors meant to be looked at (albedo, emissive)
+// are usually sRGB, while data such as normals, roughness or masks is linear
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sampler {
+    pub min_filter: GLenum,
+    pub mag_filter: GLenum,
+    pub wrap_s: GLenum,
+    pub wrap_t: GLenum,
+}
+
+pub struct Texture2D {
+    texture: Texture,
+}
+
+// Six square faces, in the order +X, -X, +Y, -Y, +Z, -Z
+pub struct Cubemap {
+    texture: Texture,
+}
+
+#[derive(Debug)]
+pub enum TextureError {
+    Image {
+        path: PathBuf,
+        source: image::ImageError,
+    },
+    // Every face of a cubemap has to be a square of the same size as the first one
+    CubemapFace {
+        face: usize,
+        size: (u32, u32),
+        expected: u32,
+    },
+    Shader(ShaderError),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TextureError::Image { path, source } => {
+                write!(f, "failed to load {}: {}", path.display(), source)
+            }
+            TextureError::CubemapFace {
+                face,
+                size,
+                expected,
+            } => write!(
+                f,
+                "cubemap face {} is {}x{}, expected {}x{}",
+                face, size.0, size.1, expected, expected
+            ),
+            TextureError::Shader(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TextureError::Image { source, .. } => Some(source),
+            TextureError::CubemapFace { .. } => None,
+            TextureError::Shader(e) => Some(e),
+        }
+    }
+}
+
+impl From<ShaderError> for TextureError {
+    fn from(e: ShaderError) -> TextureError {
+        TextureError::Shader(e)
+    }
+}
+
+impl Default for Sampler {
+    // Trilinear filtering, repeating in both directions
+    fn default() -> Sampler {
+        Sampler {
+            min_filter: gl::LINEAR_MIPMAP_LINEAR,
+            mag_filter: gl::LINEAR,
+            wrap_s: gl::REPEAT,
+            wrap_t: gl::REPEAT,
+        }
+    }
+}
+
+impl Sampler {
+    // e.g. `gl::REPEAT`, `gl::MIRRORED_REPEAT` or `gl::CLAMP_TO_EDGE`
+    pub fn wrap(self, wrap_s: GLenum, wrap_t: GLenum) -> Sampler {
+        Sampler {
+            wrap_s,
+            wrap_t,
+            ..self
+        }
+    }
+}
+
+// The internal format, pixel format, pixel type and swizzle used to upload an image. Gray images
+// are swizzled so they read as gray in shaders instead of red
+struct UploadFormat {
+    internal_format: GLenum,
+    format: GLenum,
+    pixel_type: GLenum,
+    swizzle: Option<[GLenum; 4]>,
+}
+
+const GRAY_SWIZZLE: [GLenum; 4] = [gl::RED, gl::RED, gl::RED, gl::ONE];
+const GRAY_ALPHA_SWIZZLE: [GLenum; 4] = [gl::RED, gl::RED, gl::RED, gl::GREEN];
+
+fn upload_format(image: &DynamicImage, color_space: ColorSpace) -> UploadFormat {
+    let srgb = color_space == ColorSpace::Srgb;
+    let (internal_format, format, pixel_type, swizzle) = match image {
+        DynamicImage::ImageLuma8(_) => (gl::R8, gl::RED, gl::UNSIGNED_BYTE, Some(GRAY_SWIZZLE)),
+        DynamicImage::ImageLumaA8(_) => {
+            (gl::RG8, gl::RG, gl::UNSIGNED_BYTE, Some(GRAY_ALPHA_SWIZZLE))
+        }
+        DynamicImage::ImageRgb8(_) if srgb => (gl::SRGB8, gl::RGB, gl::UNSIGNED_BYTE, None),
+        DynamicImage::ImageRgb8(_) => (gl::RGB8, gl::RGB, gl::UNSIGNED_BYTE, None),
+        DynamicImage::ImageRgba8(_) if srgb => {
+            (gl::SRGB8_ALPHA8, gl::RGBA, gl::UNSIGNED_BYTE, None)
+        }
+        DynamicImage::ImageRgba8(_) => (gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE, None),
+        DynamicImage::ImageBgr8(_) if srgb => (gl::SRGB8, gl::BGR, gl::UNSIGNED_BYTE, None),
+        DynamicImage::ImageBgr8(_) => (gl::RGB8, gl::BGR, gl::UNSIGNED_BYTE, None),
+        DynamicImage::ImageBgra8(_) if srgb => {
+            (gl::SRGB8_ALPHA8, gl::BGRA, gl::UNSIGNED_BYTE, None)
+        }
+        DynamicImage::ImageBgra8(_) => (gl::RGBA8, gl::BGRA, gl::UNSIGNED_BYTE, None),
+        DynamicImage::ImageLuma16(_) => {
+            (gl::R16, gl::RED, gl::UNSIGNED_SHORT, Some(GRAY_SWIZZLE))
+        }
+        DynamicImage::ImageLumaA16(_) => {
+            (gl::RG16, gl::RG, gl::UNSIGNED_SHORT, Some(GRAY_ALPHA_SWIZZLE))
+        }
+        DynamicImage::ImageRgb16(_) => (gl::RGB16, gl::RGB, gl::UNSIGNED_SHORT, None),
+        DynamicImage::ImageRgba16(_) => (gl::RGBA16, gl::RGBA, gl::UNSIGNED_SHORT, None),
+    };
+    UploadFormat {
+        internal_format,
+        format,
+        pixel_type,
+        swizzle,
+    }
+}
+
+// OpenGL only has sRGB formats for 8 bit RGB and RGBA, so other sRGB images are converted first.
+// Returns `None` when the image can be uploaded as is
+fn srgb_compatible(image: &DynamicImage) -> Option<DynamicImage> {
+    match image {
+        DynamicImage::ImageRgb8(_)
+        | DynamicImage::ImageRgba8(_)
+        | DynamicImage::ImageBgr8(_)
+        | DynamicImage::ImageBgra8(_) => None,
+        DynamicImage::ImageLuma8(_) | DynamicImage::ImageLuma16(_) | DynamicImage::ImageRgb16(_) => {
+            Some(DynamicImage::ImageRgb8(image.to_rgb8()))
+        }
+        DynamicImage::ImageLumaA8(_)
+        | DynamicImage::ImageLumaA16(_)
+        | DynamicImage::ImageRgba16(_) => Some(DynamicImage::ImageRgba8(image.to_rgba8())),
+    }
+}
+
+// Enough levels to go all the way down to 1x1
+fn mip_levels(width: u32, height: u32) -> i32 {
+    32 - width.max(height).max(1).leading_zeros() as i32
+}
+
+fn open_image(path: &str) -> Result<DynamicImage, TextureError> {
+    image::open(path).map_err(|source| TextureError::Image {
+        path: Path::new(path).to_path_buf(),
+        source,
+    })
+}
+
+impl Texture2D {
+    // Loads a PNG, JPEG, TGA, BMP, or any other format the `image` crate understands
+    pub unsafe fn load(path: &str, color_space: ColorSpace) -> Result<Texture2D, TextureError> {
+        Ok(Texture2D::from_image(&open_image(path)?, color_space))
+    }
+
+    // Uploads an image along with a full chain of mipmaps, and samples it with `Sampler::default()`
+    pub unsafe fn from_image(image: &DynamicImage, color_space: ColorSpace) -> Texture2D {
+        let converted = match color_space {
+            ColorSpace::Srgb => srgb_compatible(image),
+            ColorSpace::Linear => None,
+        };
+        let image = converted.as_ref().unwrap_or(image).flipv();
+        let upload = upload_format(&image, color_space);
+        let (width, height) = image.dimensions();
+        let levels = mip_levels(width, height);
+
+        let texture = Texture::new(gl::TEXTURE_2D);
+        gl::TextureStorage2D(
+            texture.id(),
+            levels,
+            upload.internal_format,
+            width as i32,
+            height as i32,
+        );
+        // Rows of RGB and gray images aren't necessarily a multiple of 4 bytes long
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+        gl::TextureSubImage2D(
+            texture.id(),
+            0,
+            0,
+            0,
+            width as i32,
+            height as i32,
+            upload.format,
+            upload.pixel_type,
+            image.as_bytes().as_ptr() as *const c_void,
+        );
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+        gl::GenerateTextureMipmap(texture.id());
+        if let Some(swizzle) = upload.swizzle {
+            let swizzle = swizzle.map(|channel| channel as GLint);
+            gl::TextureParameteriv(texture.id(), gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr());
+        }
+
+        let texture = Texture2D { texture };
+        texture.set_sampler(&Sampler::default());
+        texture
+    }
+
+    // A 1x1 texture of a single color, handy as a stand-in when a material has no texture
+    pub unsafe fn solid(color: [u8; 4], color_space: ColorSpace) -> Texture2D {
+        let pixel = image::RgbaImage::from_pixel(1, 1, image::Rgba(color));
+        Texture2D::from_image(&DynamicImage::ImageRgba8(pixel), color_space)
+    }
+
+    pub unsafe fn set_sampler(&self, sampler: &Sampler) {
+        let id = self.texture.id();
+        gl::TextureParameteri(id, gl::TEXTURE_MIN_FILTER, sampler.min_filter as GLint);
+        gl::TextureParameteri(id, gl::TEXTURE_MAG_FILTER, sampler.mag_filter as GLint);
+        gl::TextureParameteri(id, gl::TEXTURE_WRAP_S, sampler.wrap_s as GLint);
+        gl::TextureParameteri(id, gl::TEXTURE_WRAP_T, sampler.wrap_t as GLint);
+    }
+
+    pub unsafe fn bind(&self, unit: TextureUnit) {
+        self.texture.bind(unit.0);
+    }
+
+    // Binds the texture to `unit` and points the sampler uniform at it
+    pub unsafe fn bind_to(&self, uniform: &ShaderUniform, unit: TextureUnit) {
+        self.bind(unit);
+        uniform.set(&unit);
+    }
+}
+
+impl Cubemap {
+    // Loads the six faces, in the order +X, -X, +Y, -Y, +Z, -Z (right, left, top, bottom, front,
+    // back)
+    pub unsafe fn load(paths: [&str; 6], color_space: ColorSpace) -> Result<Cubemap, TextureError> {
+        let mut faces = Vec::with_capacity(6);
+        for path in paths.iter() {
+            faces.push(open_image(path)?);
+        }
+        Cubemap::from_images(&faces, color_space)
+    }
+
+    pub unsafe fn from_images(
+        faces: &[DynamicImage],
+        color_space: ColorSpace,
+    ) -> Result<Cubemap, TextureError> {
+        assert_eq!(faces.len(), 6, "a cubemap needs exactly six faces");
+        let size = faces[0].width();
+        for (face, image) in faces.iter().enumerate() {
+            if image.dimensions() != (size, size) {
+                return Err(TextureError::CubemapFace {
+                    face,
+                    size: image.dimensions(),
+                    expected: size,
+                });
+            }
+        }
+
+        // Every face has to share one format, so they are all brought to 8 bit RGBA
+        let internal_format = match color_space {
+            ColorSpace::Srgb => gl::SRGB8_ALPHA8,
+            ColorSpace::Linear => gl::RGBA8,
+        };
+        let texture = Texture::new(gl::TEXTURE_CUBE_MAP);
+        gl::TextureStorage2D(
+            texture.id(),
+            mip_levels(size, size),
+            internal_format,
+            size as i32,
+            size as i32,
+        );
+        for (face, image) in faces.iter().enumerate() {
+            let pixels = image.to_rgba8();
+            gl::TextureSubImage3D(
+                texture.id(),
+                0,
+                0,
+                0,
+                face as i32,
+                size as i32,
+                size as i32,
+                1,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr() as *const c_void,
+            );
+        }
+        Ok(Cubemap::finish(texture))
+    }
+
+    // Loads a single equirectangular (latitude/longitude) panorama and projects it onto the faces
+    pub unsafe fn load_equirectangular(
+        path: &str,
+        color_space: ColorSpace,
+        size: u32,
+    ) -> Result<Cubemap, TextureError> {
+        let equirectangular = Texture2D::load(path, color_space)?;
+        Cubemap::from_equirectangular(&equirectangular, size)
+    }
+
+    // Projects a panorama onto six faces of `size` pixels squared with a compute shader. The faces
+    // are stored as 16 bit floats, so no precision is lost to the conversion
+    pub unsafe fn from_equirectangular(
+        equirectangular: &Texture2D,
+        size: u32,
+    ) -> Result<Cubemap, TextureError> {
+        let program = ShaderBuilder::new()
+            .attach_file("./shaders/equirect_to_cubemap.comp")?
+            .link()?;
+
+        let texture = Texture::new(gl::TEXTURE_CUBE_MAP);
+        gl::TextureStorage2D(
+            texture.id(),
+            mip_levels(size, size),
+            gl::RGBA16F,
+            size as i32,
+            size as i32,
+        );
+
+        // The panorama wraps around horizontally, but not over the poles
+        equirectangular.set_sampler(&Sampler::default().wrap(gl::REPEAT, gl::CLAMP_TO_EDGE));
+        equirectangular.bind_to(
+            &ShaderUniform::new(&program, "equirectangular"),
+            TextureUnit(0),
+        );
+        // Layered, so all six faces can be written at once
+        gl::BindImageTexture(0, texture.id(), 0, gl::TRUE, 0, gl::WRITE_ONLY, gl::RGBA16F);
+        ShaderUniform::new(&program, "faces").set(&ImageUnit(0));
+
+        program.dispatch_invocations(size, size, 6);
+        // The skybox samples the faces, and the mipmaps are built from them
+        memory_barrier(MemoryBarrier(
+            gl::TEXTURE_FETCH_BARRIER_BIT | gl::TEXTURE_UPDATE_BARRIER_BIT,
+        ));
+        equirectangular.set_sampler(&Sampler::default());
+
+        Ok(Cubemap::finish(texture))
+    }
+
+    // Fills in the mipmaps and sets up sampling for a texture whose base level has been uploaded
+    unsafe fn finish(texture: Texture) -> Cubemap {
+        let id = texture.id();
+        gl::GenerateTextureMipmap(id);
+        gl::TextureParameteri(id, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as GLint);
+        gl::TextureParameteri(id, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+        for &wrap in &[gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R] {
+            gl::TextureParameteri(id, wrap, gl::CLAMP_TO_EDGE as GLint);
+        }
+        Cubemap { texture }
+    }
+
+    pub unsafe fn bind(&self, unit: TextureUnit) {
+        self.texture.bind(unit.0);
+    }
+
+    // Binds the cubemap to `unit` and points the `samplerCube` uniform at it
+    pub unsafe fn bind_to(&self, uniform: &ShaderUniform, unit: TextureUnit) {
+        self.bind(unit);
+        uniform.set(&unit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mip_chain_reaches_one_pixel() {
+        assert_eq!(mip_levels(1, 1), 1);
+        assert_eq!(mip_levels(256, 256), 9);
+        assert_eq!(mip_levels(300, 20), 9);
+    }
+
+    #[test]
+    fn only_colors_use_srgb_formats() {
+        let rgba = DynamicImage::ImageRgba8(image::RgbaImage::new(1, 1));
+        assert_eq!(upload_format(&rgba, ColorSpace::Srgb).internal_format, gl::SRGB8_ALPHA8);
+        assert_eq!(upload_format(&rgba, ColorSpace::Linear).internal_format, gl::RGBA8);
+
+        // Gray sRGB images are expanded to RGB, as there is no sRGB format with a single channel
+        let gray = DynamicImage::ImageLuma8(image::GrayImage::new(1, 1));
+        let converted = srgb_compatible(&gray).unwrap();
+        assert_eq!(upload_format(&converted, ColorSpace::Srgb).internal_format, gl::SRGB8);
+        assert!(srgb_compatible(&rgba).is_none());
+    }
+}
@@ -1,4 +1,5 @@
 use std::ffi::CString;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub unsafe fn get_gl_string(name: gl::types::GLenum) -> String {
     std::ffi::CStr::from_ptr(gl::GetString(name) as *mut i8).to_string_lossy().to_string()
@@ -30,3 +31,63 @@ pub extern "system" fn debug_callback(
     }
 }
 
+
+// Formats a time since the Unix epoch as e.g. 2021-09-14_13-37-00-042, in UTC so file names don't
+// depend on the time zone or jump around daylight saving time. It goes down to the millisecond so
+// files written within the same second don't overwrite each other, and sorts by name in time order
+pub fn format_timestamp(since_epoch: Duration) -> String {
+    let (seconds, millis) = (since_epoch.as_secs(), since_epoch.subsec_millis());
+    let (days, seconds_of_day) = ((seconds / 86400) as i64, seconds % 86400);
+
+    // Converts days since 1970-01-01 to a date, from Howard Hinnant's `civil_from_days`
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{}-{:02}-{:02}_{:02}-{:02}-{:02}-{:03}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        millis
+    )
+}
+
+// The current time, formatted by `format_timestamp`
+pub fn timestamp() -> String {
+    format_timestamp(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_of_epoch() {
+        assert_eq!(format_timestamp(Duration::ZERO), "1970-01-01_00-00-00-000");
+    }
+
+    #[test]
+    fn timestamp_of_leap_day() {
+        // 2000 is a leap year despite being divisible by 100, since it is divisible by 400
+        let leap_day = Duration::from_secs(951_782_400);
+        assert_eq!(format_timestamp(leap_day), "2000-02-29_00-00-00-000");
+    }
+
+    #[test]
+    fn timestamp_of_last_millisecond_of_year() {
+        let new_year = Duration::from_secs(1_640_995_200);
+        let last = new_year - Duration::from_millis(1);
+        assert_eq!(format_timestamp(last), "2021-12-31_23-59-59-999");
+    }
+}
@@ -0,0 +1,372 @@
+// Describing how vertex data is laid out in buffers, and turning buffers of vertices into VAOs.
+//
+// A `VertexLayout` lists the attributes found in one buffer. Interleaved vertices use a single
+// buffer whose layout describes every field of a `#[repr(C)]` vertex struct, while separate
+// buffers each get a layout with a single attribute. Either way the buffers are handed to a
+// `VertexArrayBuilder`:
+//
+//     #[repr(C)]
+//     #[derive(Clone, Copy)]
+//     struct ColoredVertex {
+//         position: glm::Vec3,
+//         color: glm::Vec4,
+//     }
+//
+//     impl Vertex for ColoredVertex {
+//         fn layout() -> VertexLayout {
+//             VertexLayout::of::<ColoredVertex>()
+//                 .attribute(VertexAttribute::new::<f32>(0, 3)
+//                     .offset(mem::offset_of!(ColoredVertex, position)))
+//                 .attribute(VertexAttribute::new::<f32>(1, 4)
+//                     .offset(mem::offset_of!(ColoredVertex, color)))
+//         }
+//     }
+//
+//     let vao = VertexArrayBuilder::new().vertices(&vertices).indices(&indices).build();
+
+use gl::types::GLenum;
+use std::{mem, os::raw::c_void};
+
+use crate::gl_object::{Buffer, VertexArray};
+use crate::shader::reflect::{self, ProgramReflection};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComponentType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+}
+
+// The Rust types vertex attribute components can be stored as
+pub trait Component: Copy {
+    const TYPE: ComponentType;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VertexAttribute {
+    pub location: u32,
+    pub component_type: ComponentType,
+    // Number of components, 1 to 4
+    pub components: i32,
+    // Whether integer components are mapped to [0, 1] or [-1, 1] when read as floats
+    pub normalized: bool,
+    // Whether integer components are read as integers (`ivec`/`uvec` inputs) rather than floats
+    pub integer: bool,
+    // 0 to advance every vertex, n to advance every n instances
+    pub divisor: u32,
+    // Byte offset of the attribute within a vertex
+    pub offset: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VertexLayout {
+    stride: usize,
+    attributes: Vec<VertexAttribute>,
+}
+
+// A vertex struct that knows its own layout
+pub trait Vertex: Copy {
+    fn layout() -> VertexLayout;
+}
+
+pub struct VertexArrayBuilder {
+    vao: VertexArray,
+    next_binding: u32,
+    attributes: Vec<VertexAttribute>,
+}
+
+impl ComponentType {
+    fn gl_type(self) -> GLenum {
+        match self {
+            ComponentType::I8 => gl::BYTE,
+            ComponentType::U8 => gl::UNSIGNED_BYTE,
+            ComponentType::I16 => gl::SHORT,
+            ComponentType::U16 => gl::UNSIGNED_SHORT,
+            ComponentType::I32 => gl::INT,
+            ComponentType::U32 => gl::UNSIGNED_INT,
+            ComponentType::F32 => gl::FLOAT,
+        }
+    }
+
+    pub fn size(self) -> usize {
+        match self {
+            ComponentType::I8 | ComponentType::U8 => 1,
+            ComponentType::I16 | ComponentType::U16 => 2,
+            ComponentType::I32 | ComponentType::U32 | ComponentType::F32 => 4,
+        }
+    }
+
+    fn is_float(self) -> bool {
+        self == ComponentType::F32
+    }
+
+    fn is_signed(self) -> bool {
+        matches!(self, ComponentType::I8 | ComponentType::I16 | ComponentType::I32)
+    }
+}
+
+macro_rules! component {
+    ($($rust_type:ty => $component_type:ident),*) => {
+        $(impl Component for $rust_type {
+            const TYPE: ComponentType = ComponentType::$component_type;
+        })*
+    };
+}
+
+component!(i8 => I8, u8 => U8, i16 => I16, u16 => U16, i32 => I32, u32 => U32, f32 => F32);
+
+impl VertexAttribute {
+    // An attribute of `components` values of type `C`. The other fields can be set with struct
+    // update syntax, e.g. `VertexAttribute { normalized: true, ..VertexAttribute::new::<u8>(2, 4) }`
+    pub fn new<C: Component>(location: u32, components: i32) -> VertexAttribute {
+        assert!((1..=4).contains(&components), "vertex attributes have 1 to 4 components");
+        VertexAttribute {
+            location,
+            component_type: C::TYPE,
+            components,
+            normalized: false,
+            integer: false,
+            divisor: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn offset(mut self, offset: usize) -> VertexAttribute {
+        self.offset = offset;
+        self
+    }
+
+    pub fn size(&self) -> usize {
+        self.component_type.size() * self.components as usize
+    }
+}
+
+impl VertexLayout {
+    // A layout for a buffer where consecutive vertices are `stride` bytes apart
+    pub fn new(stride: usize) -> VertexLayout {
+        VertexLayout {
+            stride,
+            attributes: vec![],
+        }
+    }
+
+    // A layout for a buffer of `V`s
+    pub fn of<V>() -> VertexLayout {
+        VertexLayout::new(mem::size_of::<V>())
+    }
+
+    // A layout for a buffer holding nothing but a single attribute
+    pub fn single(attribute: VertexAttribute) -> VertexLayout {
+        VertexLayout::new(attribute.size()).attribute(attribute.offset(0))
+    }
+
+    pub fn attribute(mut self, attribute: VertexAttribute) -> VertexLayout {
+        assert!(
+            attribute.offset + attribute.size() <= self.stride,
+            "vertex attribute at location {} doesn't fit within the stride",
+            attribute.location
+        );
+        self.attributes.push(attribute);
+        self
+    }
+}
+
+// Whether a vertex input of the given GLSL type can be fed from `attribute`
+fn compatible(attribute: &VertexAttribute, gl_type: GLenum) -> Result<(), &'static str> {
+    let integer_input = |signed: bool| {
+        if !attribute.integer || attribute.component_type.is_float() {
+            Err("integer inputs need an integer attribute")
+        } else if signed != attribute.component_type.is_signed() {
+            Err("the signedness of the attribute doesn't match the input")
+        } else {
+            Ok(())
+        }
+    };
+
+    match gl_type {
+        gl::DOUBLE
+        | gl::DOUBLE_VEC2
+        | gl::DOUBLE_VEC3
+        | gl::DOUBLE_VEC4
+        | gl::DOUBLE_MAT2
+        | gl::DOUBLE_MAT3
+        | gl::DOUBLE_MAT4
+        | gl::DOUBLE_MAT2x3
+        | gl::DOUBLE_MAT2x4
+        | gl::DOUBLE_MAT3x2
+        | gl::DOUBLE_MAT3x4
+        | gl::DOUBLE_MAT4x2
+        | gl::DOUBLE_MAT4x3 => Err("double inputs are not supported"),
+        gl::INT | gl::INT_VEC2 | gl::INT_VEC3 | gl::INT_VEC4 => integer_input(true),
+        gl::UNSIGNED_INT
+        | gl::UNSIGNED_INT_VEC2
+        | gl::UNSIGNED_INT_VEC3
+        | gl::UNSIGNED_INT_VEC4 => integer_input(false),
+        _ if attribute.integer => Err("float inputs can't be fed from an integer attribute"),
+        _ => Ok(()),
+    }
+}
+
+// Compares `attributes` with the vertex inputs of a program, returning a description of every
+// input they don't provide, or provide with an incompatible type
+fn check_attributes(attributes: &[VertexAttribute], program: &ProgramReflection) -> Vec<String> {
+    let mut problems = vec![];
+    // Built-in inputs such as gl_VertexID have no location and need no attribute
+    for input in program.attributes.iter().filter(|a| a.location >= 0) {
+        // Every element of an array input takes up a location of its own
+        for element in 0..input.array_size {
+            let location = input.location + element;
+            let name = if input.array_size > 1 {
+                format!("{}[{}]", reflect::base_name(&input.name), element)
+            } else {
+                input.name.clone()
+            };
+            let attribute = attributes.iter().find(|a| a.location as i32 == location);
+            let problem = match attribute {
+                None => "it has no vertex attribute",
+                Some(attribute) => match compatible(attribute, input.gl_type) {
+                    Ok(()) => continue,
+                    Err(reason) => reason,
+                },
+            };
+            problems.push(format!(
+                "input `{}` ({}) at location {}: {}",
+                name,
+                reflect::glsl_type_name(input.gl_type),
+                location,
+                problem
+            ));
+        }
+    }
+    problems
+}
+
+impl VertexArrayBuilder {
+    pub unsafe fn new() -> VertexArrayBuilder {
+        VertexArrayBuilder {
+            vao: VertexArray::new(),
+            next_binding: 0,
+            attributes: vec![],
+        }
+    }
+
+    // Uploads `data` to a new buffer, read according to `layout`
+    pub unsafe fn buffer<T: Copy>(mut self, data: &[T], layout: &VertexLayout) -> VertexArrayBuilder {
+        let buffer = Buffer::new();
+        gl::NamedBufferData(
+            buffer.id(),
+            mem::size_of_val(data) as isize,
+            data.as_ptr() as *const c_void,
+            gl::STATIC_DRAW,
+        );
+
+        // The divisor belongs to a binding point rather than an attribute, so the buffer is bound
+        // once for every distinct divisor in the layout
+        let mut divisors: Vec<u32> = layout.attributes.iter().map(|a| a.divisor).collect();
+        divisors.sort_unstable();
+        divisors.dedup();
+
+        for divisor in divisors {
+            let binding = self.next_binding;
+            self.next_binding += 1;
+            gl::VertexArrayVertexBuffer(self.vao.id(), binding, buffer.id(), 0, layout.stride as i32);
+            gl::VertexArrayBindingDivisor(self.vao.id(), binding, divisor);
+
+            for attribute in layout.attributes.iter().filter(|a| a.divisor == divisor) {
+                let vao_id = self.vao.id();
+                let gl_type = attribute.component_type.gl_type();
+                gl::EnableVertexArrayAttrib(vao_id, attribute.location);
+                if attribute.integer {
+                    gl::VertexArrayAttribIFormat(
+                        vao_id,
+                        attribute.location,
+                        attribute.components,
+                        gl_type,
+                        attribute.offset as u32,
+                    );
+                } else {
+                    gl::VertexArrayAttribFormat(
+                        vao_id,
+                        attribute.location,
+                        attribute.components,
+                        gl_type,
+                        attribute.normalized as u8,
+                        attribute.offset as u32,
+                    );
+                }
+                gl::VertexArrayAttribBinding(vao_id, attribute.location, binding);
+            }
+        }
+
+        self.attributes.extend(layout.attributes.iter().cloned());
+        self.vao.keep_buffer(buffer);
+        self
+    }
+
+    // Uploads interleaved vertices to a new buffer
+    pub unsafe fn vertices<V: Vertex>(self, vertices: &[V]) -> VertexArrayBuilder {
+        self.buffer(vertices, &V::layout())
+    }
+
+    pub unsafe fn indices(mut self, indices: &[u32]) -> VertexArrayBuilder {
+        let buffer = Buffer::new();
+        gl::NamedBufferData(
+            buffer.id(),
+            mem::size_of_val(indices) as isize,
+            indices.as_ptr() as *const c_void,
+            gl::STATIC_DRAW,
+        );
+        gl::VertexArrayElementBuffer(self.vao.id(), buffer.id());
+        self.vao.keep_buffer(buffer);
+        self
+    }
+
+    // Compares the attributes of every buffer added so far with the vertex inputs of a program,
+    // returning a description of every input they don't provide, or provide with an incompatible
+    // type
+    pub fn check(&self, program: &ProgramReflection) -> Vec<String> {
+        check_attributes(&self.attributes, program)
+    }
+
+    pub fn build(self) -> VertexArray {
+        self.vao
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_match_attributes_by_type() {
+        let floats = VertexAttribute::new::<f32>(0, 3);
+        let ints = VertexAttribute {
+            integer: true,
+            ..VertexAttribute::new::<i32>(0, 2)
+        };
+        let uints = VertexAttribute {
+            integer: true,
+            ..VertexAttribute::new::<u16>(0, 4)
+        };
+        let normalized = VertexAttribute {
+            normalized: true,
+            ..VertexAttribute::new::<u8>(0, 4)
+        };
+
+        assert!(compatible(&floats, gl::FLOAT_VEC3).is_ok());
+        assert!(compatible(&normalized, gl::FLOAT_VEC4).is_ok());
+        assert!(compatible(&ints, gl::INT_VEC2).is_ok());
+        assert!(compatible(&uints, gl::UNSIGNED_INT_VEC4).is_ok());
+
+        assert!(compatible(&ints, gl::FLOAT_VEC2).is_err());
+        assert!(compatible(&floats, gl::INT_VEC3).is_err());
+        assert!(compatible(&normalized, gl::UNSIGNED_INT_VEC4).is_err());
+        assert!(compatible(&ints, gl::UNSIGNED_INT_VEC2).is_err());
+        assert!(compatible(&uints, gl::INT_VEC4).is_err());
+        assert!(compatible(&floats, gl::DOUBLE_VEC3).is_err());
+    }
+}
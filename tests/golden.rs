@@ -0,0 +1,165 @@
+// Golden image tests: renders scenes headless and compares them against the reference images in
+// tests/golden, so changes to shaders or the camera that alter the output don't go unnoticed.
+//
+// References are only ever written by running with `UPDATE_GOLDEN=1 cargo test`, both to add the
+// reference of a new scene and to accept an intended change. A missing reference fails the test.
+// On a mismatch the rendered frame and a diff image, with changed pixels in red over a faded
+// reference, are written next to each other under target/tmp/golden.
+//
+// Headless frames are always opaque, so only the color channels are compared.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use image::{Rgb, RgbImage};
+
+const WIDTH: u32 = 320;
+const HEIGHT: u32 = 240;
+
+struct Tolerance {
+    // Channel differences up to this are ignored outright, absorbing rounding differences between
+    // drivers
+    channel: u8,
+    // How different a pixel has to look to count as changed, from 0 for identical to 1 for black
+    // against white
+    perceptual: f32,
+    // The fraction of pixels allowed to change, for edges rasterized slightly differently
+    changed_pixels: f32,
+}
+
+const TOLERANCE: Tolerance = Tolerance {
+    channel: 2,
+    perceptual: 0.05,
+    changed_pixels: 0.001,
+};
+
+// Luma and chroma of a color, which differences are measured in since they track how different
+// colors look better than RGB does
+fn yiq(pixel: &Rgb<u8>) -> (f32, f32, f32) {
+    let [r, g, b] = pixel.0.map(|channel| channel as f32 / 255.0);
+    (
+        0.298_895_3 * r + 0.586_622_5 * g + 0.114_482_2 * b,
+        0.595_978 * r - 0.274_176_1 * g - 0.321_801_9 * b,
+        0.211_470_2 * r - 0.522_617_1 * g + 0.311_146_9 * b,
+    )
+}
+
+// From 0 for identical colors to 1 for black against white
+fn perceptual_difference(a: &Rgb<u8>, b: &Rgb<u8>) -> f32 {
+    let (ay, ai, aq) = yiq(a);
+    let (by, bi, bq) = yiq(b);
+    let (y, i, q) = (ay - by, ai - bi, aq - bq);
+    let delta = 0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q;
+    (delta / 0.5053).sqrt()
+}
+
+fn is_changed(a: &Rgb<u8>, b: &Rgb<u8>, tolerance: &Tolerance) -> bool {
+    let differs = |channel: usize| a[channel].abs_diff(b[channel]) > tolerance.channel;
+    (0..3).any(differs) && perceptual_difference(a, b) > tolerance.perceptual
+}
+
+// Counts the changed pixels, and draws them in red over a faded grayscale copy of the reference
+fn compare(reference: &RgbImage, actual: &RgbImage, tolerance: &Tolerance) -> (usize, RgbImage) {
+    let mut changed = 0;
+    let diff = RgbImage::from_fn(reference.width(), reference.height(), |x, y| {
+        let (expected, pixel) = (reference.get_pixel(x, y), actual.get_pixel(x, y));
+        if is_changed(expected, pixel, tolerance) {
+            changed += 1;
+            Rgb([255, 0, 0])
+        } else {
+            let gray = (yiq(expected).0 * 255.0) as u8 / 4 + 191;
+            Rgb([gray, gray, gray])
+        }
+    });
+    (changed, diff)
+}
+
+// Where the rendered frame and the diff image of a scene end up
+fn output_dir(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden").join(name)
+}
+
+// Renders one frame of the scene with the gloom-rs binary, `time` seconds into the animation
+fn render(name: &str, args: &[&str], time: f32) -> RgbImage {
+    let output = output_dir(name);
+    let size = format!("{}x{}", WIDTH, HEIGHT);
+    let time = time.to_string();
+    // Shaders are loaded relative to the working directory. The program cache goes in the target
+    // directory, so tests neither read nor fill the user's cache
+    let cache = Path::new(env!("CARGO_TARGET_TMPDIR")).join("cache");
+    let result = Command::new(env!("CARGO_BIN_EXE_gloom-rs"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env("XDG_CACHE_HOME", &cache)
+        .args(args)
+        .args(["--headless", "--size", &size, "--time", &time, "--output"])
+        .arg(&output)
+        .output()
+        .expect("could not run gloom-rs");
+    assert!(
+        result.status.success(),
+        "rendering {} failed with {}:\n{}{}",
+        name,
+        result.status,
+        String::from_utf8_lossy(&result.stdout),
+        String::from_utf8_lossy(&result.stderr)
+    );
+
+    let frame = output.join("frame_0000.png");
+    image::open(&frame)
+        .unwrap_or_else(|e| panic!("could not read {}: {}", frame.display(), e))
+        .to_rgb8()
+}
+
+fn check(name: &str, args: &[&str], time: f32) {
+    let actual = render(name, args, time);
+    let reference_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name)
+        .with_extension("png");
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        actual.save(&reference_path).unwrap();
+        println!("Wrote reference image {}", reference_path.display());
+        return;
+    }
+    assert!(
+        reference_path.exists(),
+        "{} is missing. Run with UPDATE_GOLDEN=1 to write it from the current output",
+        reference_path.display()
+    );
+
+    let reference = image::open(&reference_path)
+        .unwrap_or_else(|e| panic!("could not read {}: {}", reference_path.display(), e))
+        .to_rgb8();
+    assert_eq!(
+        reference.dimensions(),
+        actual.dimensions(),
+        "{} is not the size the scene is rendered at",
+        reference_path.display()
+    );
+
+    let (changed, diff) = compare(&reference, &actual, &TOLERANCE);
+    let allowed = (TOLERANCE.changed_pixels * (WIDTH * HEIGHT) as f32) as usize;
+    if changed > allowed {
+        let diff_path = output_dir(name).join("diff.png");
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "{} pixels of {} differ from {}, more than the {} allowed. They are marked in {}",
+            changed,
+            name,
+            reference_path.display(),
+            allowed,
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+fn triangles() {
+    check("triangles", &[], 0.0);
+}
+
+#[test]
+fn obj_cube() {
+    check("obj_cube", &["tests/scenes/cube.obj"], 0.0);
+}